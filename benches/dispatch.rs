@@ -0,0 +1,296 @@
+//! Compares the old interpreter (a `Vec<Vec<Instruction>>` where every step cloned the instruction, strings and
+//! all, and looked natives up by name) with the current `Runtime` on the same programs. `OldRuntime` below is the
+//! dispatch loop and `run_inst` from before the flat `Program`, only reading and writing buffers instead of the
+//! terminal. There's no benchmark framework here, so run it with `cargo bench --bench dispatch` and read the
+//! numbers.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::io::{BufRead, Cursor, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dango_core::dango_errors::RuntimeError;
+use dango_core::dango_runtime::Value;
+use dango_core::dango_runtime::instructions::{Instruction, Program};
+use dango_core::dango_runtime::runtime::Runtime;
+
+const PASSES: usize = 20;
+
+static FIB: &str = include_str!("../tests/fib.dango");
+
+// Counts to 100000, with a `remove (text)` comment in the loop like `tests/f.dango` has
+static LOOP: &str = "(0)----
+(j)(2)(while)(<)(100000)---- fetch 0 (+)(1)---- remove (i = i + 1)----";
+
+// What `Instruction` looked like when text and function names were owned `String`s
+#[derive(Clone)]
+enum OldInstruction {
+    FnCall(String),
+    Text(String),
+    Other(Instruction),
+}
+
+type OldNative = fn(&mut OldRuntime) -> Result<Value, RuntimeError>;
+
+struct OldRuntime {
+    code: Vec<Vec<OldInstruction>>,
+    line: usize,
+    index: usize,
+    in_while: bool,
+    natives: HashMap<String, OldNative>,
+    stack: Vec<Value>,
+
+    input: Cursor<String>,
+    output: Vec<u8>,
+}
+
+impl OldRuntime {
+    fn new(program: &Program, input: &str) -> Self {
+        let code = (0..program.lines())
+            .map(|line| program.get_line(line).iter()
+                .map(|instruction| match *instruction {
+                    Instruction::FnCall(id) => OldInstruction::FnCall(program.string(id).to_string()),
+                    Instruction::Text(id) => OldInstruction::Text(program.string(id).to_string()),
+                    instruction => OldInstruction::Other(instruction),
+                })
+                .collect())
+            .collect();
+
+        let mut natives = HashMap::new();
+        natives.insert("io-input".to_string(), old_io_input as OldNative);
+
+        Self {
+            code,
+            line: 0,
+            index: 0,
+            in_while: false,
+            natives,
+            stack: vec![],
+            input: Cursor::new(input.to_string()),
+            output: vec![],
+        }
+    }
+
+    fn run(&mut self) -> Result<Value, RuntimeError> {
+        while self.line < self.code.len() {
+            self.index = 0;
+            while self.index < self.code[self.line].len() {
+                self.run_inst(self.code[self.line][self.index].clone())?;
+            }
+
+            if !self.in_while {
+                self.line += 1;
+            }
+        }
+
+        Ok(self.stack.pop().unwrap_or(Value::Nil))
+    }
+
+    fn pop(&mut self) -> Result<Value, RuntimeError> {
+        self.stack.pop().ok_or(RuntimeError::StackUnderflow)
+    }
+
+    fn peek(&self, depth: usize) -> Result<&Value, RuntimeError> {
+        self.stack.len().checked_sub(depth + 1)
+            .and_then(|index| self.stack.get(index))
+            .ok_or(RuntimeError::StackUnderflow)
+    }
+
+    fn run_inst(&mut self, instruction: OldInstruction) -> Result<(), RuntimeError> {
+        self.index += 1;
+
+        let instruction = match instruction {
+            OldInstruction::FnCall(name) => {
+                let Some(function) = self.natives.get(&name) else {
+                    return Err(RuntimeError::NonexistentFunction(name));
+                };
+
+                let value = function(self)?;
+                self.stack.push(value);
+                return Ok(());
+            }
+            // Raw text is an `Arc<str>` now, so this copies once more than the old `String` did
+            OldInstruction::Text(text) => {
+                self.stack.push(Value::RawText(Arc::from(text)));
+                return Ok(());
+            }
+            OldInstruction::Other(instruction) => instruction,
+        };
+
+        match instruction {
+            Instruction::Add => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+
+                match (a, b) {
+                    (Value::Int(a), Value::Int(b)) => self.stack.push(Value::Int(a.wrapping_add(b))),
+                    (Value::Int(a), Value::Float(b)) => self.stack.push(Value::Float(a as f64 + b)),
+                    (Value::Float(a), Value::Int(b)) => self.stack.push(Value::Float(a + b as f64)),
+                    (Value::Float(a), Value::Float(b)) => self.stack.push(Value::Float(a + b)),
+                    (Value::String(a), Value::String(b)) => self.stack.push(Value::String(a + b.as_str())),
+                    _ => return Err(RuntimeError::IncorrectOperationTypes("addition".to_string())),
+                }
+            }
+            Instruction::CharFromCodePoint => match self.pop()? {
+                Value::Int(codepoint) => match u32::try_from(codepoint).ok().and_then(char::from_u32) {
+                    Some(character) => self.stack.push(Value::String(character.to_string())),
+                    None => return Err(RuntimeError::NotACodePoint(Some(codepoint))),
+                }
+                _ => return Err(RuntimeError::NotACodePoint(None)),
+            }
+            Instruction::Eat => {
+                let value = self.pop()?;
+                let _ = write!(self.output, "{}", value);
+                let _ = self.output.flush();
+            }
+            Instruction::Equal => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+
+                self.stack.push(Value::Int((a == b).into()));
+            }
+            Instruction::Fetch(depth) => self.stack.push(self.peek(depth)?.clone()),
+            Instruction::Float(value) => self.stack.push(Value::Float(value)),
+            Instruction::Greater => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+
+                self.stack.push(Value::Int((a > b).into()));
+            }
+            Instruction::Int(value) => self.stack.push(Value::Int(value)),
+            Instruction::Jump => {
+                self.in_while = false;
+
+                match self.pop()? {
+                    Value::Int(offset) => {
+                        self.line = offset as usize - 1;
+                        self.index = 0;
+                    }
+                    _ => return Err(RuntimeError::CustomError("cannot jump with a non-integer offset".to_string())),
+                }
+            }
+            Instruction::Less => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+
+                self.stack.push(Value::Int((a < b).into()));
+            }
+            Instruction::Nop => {}
+            Instruction::NotEqual => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+
+                self.stack.push(Value::Int((a != b).into()));
+            }
+            Instruction::Null => self.stack.push(Value::Nil),
+            Instruction::Remove => {
+                // Neither benchmark removes from a dango, so this only drops the comment
+                self.pop()?;
+            }
+            Instruction::Stringify => match self.pop()? {
+                Value::RawText(text) => self.stack.push(Value::String(text.to_string())),
+                value => self.stack.push(Value::String(value.to_string())),
+            }
+            Instruction::Subtract => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+
+                match (a, b) {
+                    (Value::Int(a), Value::Int(b)) => self.stack.push(Value::Int(a.wrapping_sub(b))),
+                    _ => return Err(RuntimeError::IncorrectOperationTypes("subtraction".to_string())),
+                }
+            }
+            Instruction::ToInt => match self.pop()? {
+                Value::Int(x) => self.stack.push(Value::Int(x)),
+                Value::Float(x) => self.stack.push(Value::Int(x as i64)),
+                Value::String(string) => match string.parse::<i64>() {
+                    Ok(value) => self.stack.push(Value::Int(value)),
+                    Err(_) => self.stack.push(Value::Nil),
+                }
+                _ => self.stack.push(Value::Nil),
+            }
+            Instruction::While => {
+                self.in_while = match self.pop()? {
+                    Value::Int(value) => value != 0,
+                    Value::Float(value) => value != 0.0,
+                    Value::String(string) => !string.is_empty(),
+                    Value::Dango(dango) => !dango.is_empty(),
+                    _ => false,
+                };
+
+                if !self.in_while {
+                    self.index = 0;
+                    self.line += 1;
+                }
+            }
+            _ => unimplemented!("not used by the benchmarks"),
+        }
+
+        Ok(())
+    }
+}
+
+fn old_io_input(runtime: &mut OldRuntime) -> Result<Value, RuntimeError> {
+    if let Ok(Value::Int(mode)) = runtime.peek(0).cloned() {
+        runtime.pop()?;
+        if mode == 1 {
+            let prompt = runtime.pop()?;
+            let _ = write!(runtime.output, "{}", prompt);
+        }
+    }
+
+    let mut line = String::new();
+    let _ = runtime.input.read_line(&mut line);
+    line.truncate(line.trim_end_matches(['\r', '\n']).len());
+
+    Ok(Value::String(line))
+}
+
+fn new_runtime(input: &str) -> Runtime {
+    let mut runtime = Runtime::new();
+    dango_core::dango_runtime::stdlib::load_io(&mut runtime);
+    runtime.set_stdin(Box::new(Cursor::new(input.to_string())));
+    runtime.set_stdout(Box::new(std::io::sink()));
+    runtime
+}
+
+fn compile(source: &str) -> Program {
+    dango_core::dango_utils::compile_str(source).unwrap_or_else(|_| panic!("benchmark program doesn't compile"))
+}
+
+fn time(name: &str, f: impl FnOnce()) -> Duration {
+    let start = Instant::now();
+    f();
+    let elapsed = start.elapsed();
+
+    println!("{:<34} {:>10.3} ms", name, elapsed.as_secs_f64() * 1000.0);
+    elapsed
+}
+
+// Runs `source` with both interpreters `PASSES` times, compiling everything before the clock starts
+fn compare(name: &str, source: &str, input: &str) {
+    let mut old_runs = (0..PASSES).map(|_| OldRuntime::new(&compile(source), input)).collect::<Vec<_>>();
+    let new_runs = (0..PASSES).map(|_| (new_runtime(input), compile(source))).collect::<Vec<_>>();
+
+    let old = time(&format!("{}, old interpreter", name), || for runtime in &mut old_runs {
+        black_box(runtime.run()).unwrap_or_else(|err| panic!("old interpreter failed: {}", err));
+    });
+    let new = time(&format!("{}, new interpreter", name), || for (mut runtime, program) in new_runs {
+        black_box(runtime.run(program)).unwrap_or_else(|err| panic!("new interpreter failed: {}", err));
+    });
+
+    // Both have to have done the same work for the times to mean anything
+    let output = String::from_utf8_lossy(&old_runs[0].output);
+    assert!(input.is_empty() || output.contains("F(92) = 7540113804746346429"), "old interpreter went wrong");
+
+    println!("{:<34} {:>10.2}x", "speedup", old.as_secs_f64() / new.as_secs_f64());
+}
+
+fn main() {
+    // Every Fibonacci number `fib.dango` accepts, then `exit`
+    let input = (0..=92).map(|n| format!("{}\n", n)).collect::<String>() + "exit\n";
+
+    compare("fib.dango", FIB, &input);
+    compare("count to 100000", LOOP, "");
+}
@@ -0,0 +1,138 @@
+//! Errors as plain data, for tools like editors and CI that need to read them without parsing messages.
+
+use std::fmt::Write;
+
+use super::{CompileError, RuntimeError, RuntimeTrace, SourceLocation};
+use super::render::dumpling_width;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub kind: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// The name of the file the error is in, when the program is made of several files.
+    pub file: Option<String>,
+    /// Where the offending dumpling starts, or `None` for errors at the end or outside of the program.
+    pub start: Option<SourceLocation>,
+    /// The column just after the offending dumpling, on the same line as `start`.
+    pub end: Option<SourceLocation>,
+    /// The columns of `start` and `end` in UTF-16 code units instead of `char`s, which is what most editors count.
+    pub utf16_columns: Option<(usize, usize)>,
+}
+
+impl Diagnostic {
+    pub fn from_compile_error(err: &CompileError, source: &str) -> Self {
+        let start = (err.line > 0 && err.column > 0).then(|| SourceLocation::new(err.line, err.column));
+
+        Self {
+            code: err.kind.code(),
+            kind: err.kind.name(),
+            severity: Severity::Error,
+            message: err.kind.to_string(),
+            file: None,
+            start,
+            end: start.map(|start| end_of(start, source)),
+            utf16_columns: start.map(|start| utf16_columns(start, source)),
+        }
+    }
+
+    pub fn from_runtime_error(err: &RuntimeError, trace: Option<&RuntimeTrace>, source: &str) -> Self {
+        let start = trace.and_then(|trace| trace.location);
+
+        // Tools only get the message, so it has to say what caused the error too
+        let mut message = err.message();
+        let mut cause = std::error::Error::source(err);
+        while let Some(err) = cause {
+            let _ = write!(message, ": {}", err);
+            cause = err.source();
+        }
+
+        Self {
+            code: err.code(),
+            kind: err.name(),
+            severity: Severity::Error,
+            message,
+            file: None,
+            start,
+            end: start.map(|start| end_of(start, source)),
+            utf16_columns: start.map(|start| utf16_columns(start, source)),
+        }
+    }
+
+    /// Serializes the diagnostic as a single line JSON object. Missing positions are `null`.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+
+        let _ = write!(json, "{{\"code\":{},\"kind\":{},\"severity\":{},\"message\":{}",
+            json_string(self.code), json_string(self.kind), json_string(self.severity.as_str()), json_string(&self.message));
+
+        let _ = match &self.file {
+            Some(file) => write!(json, ",\"file\":{}", json_string(file)),
+            None => write!(json, ",\"file\":null"),
+        };
+
+        let (utf16_start, utf16_end) = self.utf16_columns.unzip();
+        for (name, location, utf16_column) in [("", self.start, utf16_start), ("end_", self.end, utf16_end)] {
+            let _ = match location {
+                Some(location) => write!(json, ",\"{}line\":{},\"{}column\":{}", name, location.line, name, location.column),
+                None => write!(json, ",\"{}line\":null,\"{}column\":null", name, name),
+            };
+            let _ = match utf16_column {
+                Some(column) => write!(json, ",\"{}utf16_column\":{}", name, column),
+                None => write!(json, ",\"{}utf16_column\":null", name),
+            };
+        }
+
+        json.push('}');
+        json
+    }
+}
+
+fn end_of(start: SourceLocation, source: &str) -> SourceLocation {
+    let text = source.lines().nth(start.line - 1).unwrap_or("");
+
+    SourceLocation { column: start.column + dumpling_width(text, start.column), ..start }
+}
+
+fn utf16_columns(start: SourceLocation, source: &str) -> (usize, usize) {
+    let text = source.lines().nth(start.line - 1).unwrap_or("");
+    let utf16_column = |column: usize| text.chars().take(column - 1).map(char::len_utf16).sum::<usize>() + 1;
+
+    (utf16_column(start.column), utf16_column(end_of(start, source).column))
+}
+
+fn json_string(string: &str) -> String {
+    let mut json = String::with_capacity(string.len() + 2);
+    json.push('"');
+
+    for c in string.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+    json
+}
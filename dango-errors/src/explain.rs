@@ -0,0 +1,465 @@
+//! Long-form explanations for error codes, shown by `dango explain <code>`.
+
+/// Every error code, in order.
+pub const CODES: &[&str] = &[
+    "D0001", "D0002", "D0003", "D0004", "D0005", "D0006", "D0007", "D0008", "D0009", "D0010",
+    "D0011", "D0012", "D0013", "D0014", "D0015", "D0016", "D0017", "D0018", "D0019", "D0020",
+    "D0021", "D0022",
+    "D0101", "D0102", "D0103", "D0104", "D0105", "D0106", "D0107", "D0108", "D0109", "D0110",
+    "D0111", "D0112", "D0113", "D0114", "D0115", "D0116", "D0117", "D0118", "D0119", "D0120",
+    "D0121", "D0122", "D0123",
+];
+
+/// Explains what the error with `code` means and how to fix it, with an example. Codes are matched without
+/// caring about case, so `d0001` works too.
+pub fn explain(code: &str) -> Option<&'static str> {
+    let explanation = match code.to_ascii_uppercase().as_str() {
+        "D0001" => "\
+A compile error with a custom message.
+
+These aren't made by Dango itself, but by tools built on top of it. The message
+says what went wrong.",
+        "D0002" => "\
+A word outside of a dumpling is not a keyword.
+
+Outside of dumplings, only sticks and the keywords `eat`, `fetch`, `remove` and
+`skewer` are allowed.
+
+Example:
+
+    eats (1)----
+
+`eats` is not a keyword. Use `eat` instead:
+
+    eat (1)----",
+        "D0003" => "\
+A comment inside of a dango goes on past the end of the line.
+
+Comments can span multiple lines, but not in the middle of a dango, since the
+dango would end up split over two lines.
+
+Example:
+
+    (+)(1) ;: add one
+    to two :; (2)----
+
+Close the comment on the line it was opened on, or move it out of the dango:
+
+    (+)(1) ;: add one to two :; (2)----",
+        "D0004" => "\
+A dumpling was opened but the line ended before it was closed.
+
+Dumplings have to fit on one line.
+
+Example:
+
+    (')(Hello,
+    world!)----
+
+Put the whole dumpling on one line:
+
+    (')(Hello, world!)----",
+        "D0005" => "\
+Dumplings have to be held together by a stick.
+
+Example:
+
+    (+)(1)(2)
+
+Add a stick (`----`) after the last dumpling:
+
+    (+)(1)(2)----",
+        "D0006" => "\
+A stick has no dumplings on it.
+
+Example:
+
+    eat ----
+
+Sticks only make sense right after dumplings:
+
+    eat (1)----",
+        "D0007" => "\
+A comment was opened with `;:`, but the program ended before it was closed.
+
+Example:
+
+    eat (1)---- ;: print one
+
+Close every comment with a `:;`:
+
+    eat (1)---- ;: print one :;",
+        "D0008" => "\
+A dumpling was opened with `(`, but the program ended before it was closed with
+`)`.
+
+Example:
+
+    (+)(1)(2
+
+Close the dumpling and add a stick:
+
+    (+)(1)(2)----",
+        "D0009" => "\
+`fetch` and `skewer` have to be followed by a number.
+
+Example:
+
+    fetch (0)----
+
+The number is written without a dumpling:
+
+    fetch 0",
+        "D0010" => "\
+The program ended right after `fetch` or `skewer`, before their number.
+
+Example:
+
+    (1)---- fetch
+
+Say which value to fetch:
+
+    (1)---- fetch 0",
+        "D0011" => "\
+An escaped dumpling contains a `\\` that isn't part of a known escape.
+
+Escaped dumplings start with `\\(` and understand these escapes:
+
+    \\(  \\)  \\\\  \\n  \\t  \\r  \\0  \\u{...}
+
+Example:
+
+    (')\\(C:\\Users)----
+
+Write a backslash as `\\\\`:
+
+    (')\\(C:\\\\Users)----",
+        "D0012" => "\
+A `\\u` escape isn't a valid Unicode code point.
+
+It needs 1 to 6 hexadecimal digits in braces, and the code point can't be a
+surrogate or larger than 10FFFF.
+
+Example:
+
+    (')\\(\\u2764)----
+
+Put the digits in braces:
+
+    (')\\(\\u{2764})----",
+        "D0013" => "\
+An integer literal is too big or too small for a 64-bit integer.
+
+Integers go from -9223372036854775808 to 9223372036854775807.
+
+Example:
+
+    (0x1_0000_0000_0000_0000)----
+
+Use a float if the number doesn't have to be exact:
+
+    (18446744073709551616.0)----",
+        "D0014" => "\
+Two lines are declared with the same label.
+
+A label can only point at one line.
+
+Example:
+
+    label loop (1)----
+    label loop (2)----
+
+Give every label its own name:
+
+    label one (1)----
+    label two (2)----",
+        "D0015" => "\
+`(j)` jumps to a label that isn't declared anywhere in the program.
+
+Dumplings like `(@start)` that aren't jumped to right away are left as text, so
+only jumps are checked.
+
+Example:
+
+    (j)(@start)----
+
+Declare the label with `label` on the line that `(@start)` should point at:
+
+    label start (j)(@start)----",
+        "D0016" => "\
+`label` has to be followed by the name of the label.
+
+Example:
+
+    label (loop)
+
+The name is written without a dumpling:
+
+    label loop",
+        "D0017" => "\
+A jump goes to a line that doesn't exist.
+
+Lines start at 1, and the line after the last one ends the program. Jumps to a
+line written right next to `(j)` are checked before the program runs, others
+fail with D0123 when they happen.
+
+Example:
+
+    (j)(0)----
+
+Jump to a line that exists, or give it a label:
+
+    label start (j)(@start)----",
+        "D0018" => "\
+`import` has to be followed by the path of the file to import.
+
+Example:
+
+    import
+
+Paths are relative to the file with the `import`. Paths with spaces or sticks
+in them go in a dumpling:
+
+    import lib/math.dango
+    import (my-lib.dango)",
+        "D0019" => "\
+A file couldn't be imported.
+
+The message says why, like when the file doesn't exist. Paths are relative to
+the directory of the file with the `import`, not the directory Dango was run
+from.
+
+Imports also only work in programs that are run from a file, and not in the
+REPL.",
+        "D0020" => "\
+A file ends up importing itself, through the files that it imports.
+
+Every import puts a copy of the imported file into the program, so files that
+import each other would never end.
+
+Example:
+
+    ;: a.dango :;
+    import b.dango
+
+    ;: b.dango :;
+    import a.dango
+
+Move the code that both files need into a third file that they both import.",
+        "D0021" => "\
+An `import` shares its line with something else.
+
+An imported file runs in place of the line with the `import`, and then goes on
+at the start of the next line, so anything else on that line would never run.
+
+Example:
+
+    eat (1)---- import lib.dango
+
+Put the `import` on its own line. Comments are fine:
+
+    import lib.dango ;: prints things :;
+    eat (1)----",
+        "D0022" => "\
+A file that is imported jumps to a line that is only known when it runs.
+
+An imported file is linked in after the file that imports it, so its line
+numbers are moved. Jumps to a line number like `(j)(3)` or to a label like
+`(j)(@loop)` are moved along with it, but a line number that is computed, like
+`(j)(+)(1)(2)`, can't be, and would land on a line of another file.
+
+Example:
+
+    ;: lib.dango :;
+    (j)(+)(1)(2)----
+
+Jump to a line number or a label instead:
+
+    ;: lib.dango :;
+    (j)(3)----",
+        "D0101" => "\
+A runtime error with a custom message.
+
+These aren't made by Dango itself, but by native functions that the program
+calls. The message says what went wrong.",
+        "D0102" => "\
+An operator was used on values it doesn't work with.
+
+Example:
+
+    (+)(1)(')(one)----
+
+An integer can't be added to a string. Convert the integer to a string first:
+
+    (+)(')(1)(')(one)----",
+        "D0103" => "\
+A native function was given an argument of the wrong type.
+
+Arguments are counted from the dumpling closest to the function.
+
+Example:
+
+    (:math-sqrt)(')(four)----
+
+`:math-sqrt` needs a number:
+
+    (:math-sqrt)(4)----",
+        "D0104" => "\
+The values on the stack would take up more memory than the runtime allows.
+
+The limit is set by whoever runs the program. Pop values that are no longer
+needed, or make smaller ones.",
+        "D0105" => "\
+The program calls a function that isn't registered.
+
+Nothing runs when this happens, since functions are checked before the program
+starts.
+
+Example:
+
+    (:math-squareroot)(4)----
+
+Check the spelling, and that the library the function is in is loaded:
+
+    (:math-sqrt)(4)----",
+        "D0106" => "\
+The program calls several functions that aren't registered.
+
+This is the same as D0105, for when more than one function is missing.",
+        "D0107" => "\
+`('c)` was used on a value that isn't a Unicode code point.
+
+Example:
+
+    ('c)(-1)----
+
+Only integers that are valid code points can be turned into characters:
+
+    ('c)(65)----",
+        "D0108" => "\
+There is no paused program to resume.
+
+Programs only pause when they run out of fuel. Resuming a program that finished
+or stopped with an error does nothing.",
+        "D0109" => "\
+The program ran out of fuel before it finished.
+
+Every instruction costs one unit of fuel. The runtime can be given more fuel and
+resumed from where it stopped.",
+        "D0110" => "\
+A value was pushed when the stack was already full.
+
+The stack limit is set by whoever runs the program. This usually means that a
+loop keeps pushing values without popping them.
+
+Example:
+
+    (j)(1)(0)----
+
+This loop pushes a `0` every time around and never pops it.",
+        "D0111" => "\
+A value was popped when the stack was already empty.
+
+Example:
+
+    (+)(1)----
+
+`(+)` needs two values, but only `(1)` is on the stack:
+
+    (+)(1)(2)----",
+        "D0112" => "\
+`remove` was used on an empty dango.
+
+Check the length of a dango with `(len)` before taking dumplings off of it.",
+        "D0113" => "\
+Two dango were added, but together they would have more than five dumplings.
+
+Example:
+
+    (+)---- skewer 3 (1)(2)(3)---- skewer 3 (4)(5)(6)----
+
+Dango hold at most five dumplings. Split the values over more dango instead.",
+        "D0114" => "\
+`skewer 0` would make a dango with no dumplings, which is just a stick.
+
+Example:
+
+    skewer 0
+
+Skewer at least one value:
+
+    skewer 1 (1)----",
+        "D0115" => "\
+`(j)` was given something other than a line number.
+
+Example:
+
+    (j)(')(two)----
+
+Jump with an integer:
+
+    (j)(2)----",
+        "D0116" => "\
+Reading or writing failed, like when printing to a closed pipe or writing to a
+file in a folder that doesn't exist.
+
+The message says which operation failed, and the error from the operating system
+is shown below it.",
+        "D0117" => "\
+A string was multiplied by a negative number.
+
+Example:
+
+    (*)(-1)(')(ab)----
+
+Repeat strings zero or more times:
+
+    (*)(2)(')(ab)----",
+        "D0118" => "\
+`:io-write` was asked to write to something that can't be written to.
+
+Example:
+
+    (:io-write)(')(stdin)(')(hello)----
+
+Write to `stdout`, `stderr` or a file instead:
+
+    (:io-write)(')(stdout)(')(hello)----",
+        "D0119" => "\
+A native function was called while it was already running.
+
+This can only happen in native functions that run Dango code themselves, and
+means that the function called itself.",
+        "D0120" => "\
+`skewer` was asked for more than five dumplings.
+
+Example:
+
+    skewer 6
+
+Dango hold at most five dumplings:
+
+    skewer 5",
+        "D0121" => "\
+The system clock is set to before 1970, so `:chrono-now` can't tell the time.",
+        "D0122" => "\
+A native function failed because of something outside of Dango.
+
+The message says what the function was doing, and the error it got from the
+host is shown below it.",
+        "D0123" => "\
+A jump went to a line that doesn't exist.
+
+This is the same as D0017, for jumps to a line that is only known while the
+program runs.
+
+Example:
+
+    (j)(-)(1)(1)----
+
+Check the line number before jumping to it.",
+        _ => return None,
+    };
+
+    Some(explanation)
+}
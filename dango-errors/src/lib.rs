@@ -1,8 +1,53 @@
+pub mod diagnostic;
+pub mod explain;
+pub mod render;
+pub mod source_map;
 
-#[derive(PartialEq)]
+use std::error::Error;
+use std::sync::Arc;
+
+use source_map::{SourceId, SourceMap};
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum DangoError {
     Compile(Vec<CompileError>),
-    Runtime(RuntimeError),
+    // The trace is `None` for errors that happen before the program starts, like a missing function. It is
+    // boxed so that results carrying a `DangoError` stay small
+    Runtime(RuntimeError, Option<Box<RuntimeTrace>>),
+}
+
+impl DangoError {
+    /// Turns the error into diagnostics that can be serialized. `source` is only used to find where the
+    /// offending dumplings end.
+    pub fn diagnostics(&self, source: &str) -> Vec<diagnostic::Diagnostic> {
+        match self {
+            Self::Compile(errors) => errors.iter()
+                .map(|err| diagnostic::Diagnostic::from_compile_error(err, source))
+                .collect(),
+            Self::Runtime(err, trace) => vec![diagnostic::Diagnostic::from_runtime_error(err, trace.as_deref(), source)],
+        }
+    }
+
+    /// Like `diagnostics`, but for programs made of several files, which also get the name of their file.
+    pub fn diagnostics_in(&self, sources: &SourceMap) -> Vec<diagnostic::Diagnostic> {
+        let diagnostic = |diagnostic: diagnostic::Diagnostic, file| diagnostic::Diagnostic {
+            file: Some(sources.name(file).to_string()),
+            ..diagnostic
+        };
+
+        match self {
+            Self::Compile(errors) => errors.iter()
+                .map(|err| diagnostic(diagnostic::Diagnostic::from_compile_error(err, sources.source(err.file)), err.file))
+                .collect(),
+            Self::Runtime(err, trace) => {
+                let Some(file) = trace.as_ref().and_then(|trace| trace.location).map(|location| location.file) else {
+                    return vec![diagnostic::Diagnostic::from_runtime_error(err, trace.as_deref(), "")];
+                };
+
+                vec![diagnostic(diagnostic::Diagnostic::from_runtime_error(err, trace.as_deref(), sources.source(file)), file)]
+            }
+        }
+    }
 }
 
 impl std::fmt::Display for DangoError {
@@ -14,55 +59,127 @@ impl std::fmt::Display for DangoError {
                 }
                 Ok(())
             },
-            Self::Runtime(err) => write!(f, "{err}"),
+            Self::Runtime(err, None) => write!(f, "{err}"),
+            Self::Runtime(err, Some(trace)) => write!(f, "{err}\n{trace}"),
         }
     }
 }
 
-impl std::fmt::Debug for DangoError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Display::fmt(self, f)
+impl Error for DangoError {
+    // `Display` already includes the runtime error itself, so skip straight to whatever caused it
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Compile(_) => None,
+            Self::Runtime(err, _) => err.source(),
+        }
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum CompileErrorKind {
+    ComputedJumpInImport,
     CustomError(String),
+    DuplicateLabel(String),
+    ExpectedCount(String),
+    CyclicImport(String),
+    ExpectedLabel,
+    ExpectedPath,
+    ImportFailed(String, String),
+    ImportNotAlone,
+    IntegerOverflow(String),
+    InvalidEscape(String),
     InvalidToken(String),
+    InvalidUnicodeEscape(String),
+    JumpOutOfRange(i64, usize),
+    MissingCount(String),
     MultilineComment,
     MultilineDumpling,
     OrphanedDumpling,
     OrphanedStick,
+    UndefinedLabel(String),
     UnterminatedComment,
     UnterminatedDumpling,
 }
 
-#[derive(PartialEq)]
+impl CompileErrorKind {
+    /// A code that stays the same across releases, unlike the message, so tools can match on it.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ComputedJumpInImport => "D0022",
+            Self::CustomError(_) => "D0001",
+            Self::DuplicateLabel(_) => "D0014",
+            Self::ExpectedCount(_) => "D0009",
+            Self::CyclicImport(_) => "D0020",
+            Self::ExpectedLabel => "D0016",
+            Self::ExpectedPath => "D0018",
+            Self::ImportFailed(..) => "D0019",
+            Self::ImportNotAlone => "D0021",
+            Self::IntegerOverflow(_) => "D0013",
+            Self::InvalidEscape(_) => "D0011",
+            Self::InvalidToken(_) => "D0002",
+            Self::InvalidUnicodeEscape(_) => "D0012",
+            Self::JumpOutOfRange(..) => "D0017",
+            Self::MissingCount(_) => "D0010",
+            Self::MultilineComment => "D0003",
+            Self::MultilineDumpling => "D0004",
+            Self::OrphanedDumpling => "D0005",
+            Self::OrphanedStick => "D0006",
+            Self::UndefinedLabel(_) => "D0015",
+            Self::UnterminatedComment => "D0007",
+            Self::UnterminatedDumpling => "D0008",
+        }
+    }
+
+    /// The name of the variant, for tools that would rather not deal with codes.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ComputedJumpInImport => "ComputedJumpInImport",
+            Self::CustomError(_) => "CustomError",
+            Self::DuplicateLabel(_) => "DuplicateLabel",
+            Self::ExpectedCount(_) => "ExpectedCount",
+            Self::CyclicImport(_) => "CyclicImport",
+            Self::ExpectedLabel => "ExpectedLabel",
+            Self::ExpectedPath => "ExpectedPath",
+            Self::ImportFailed(..) => "ImportFailed",
+            Self::ImportNotAlone => "ImportNotAlone",
+            Self::IntegerOverflow(_) => "IntegerOverflow",
+            Self::InvalidEscape(_) => "InvalidEscape",
+            Self::InvalidToken(_) => "InvalidToken",
+            Self::InvalidUnicodeEscape(_) => "InvalidUnicodeEscape",
+            Self::JumpOutOfRange(..) => "JumpOutOfRange",
+            Self::MissingCount(_) => "MissingCount",
+            Self::MultilineComment => "MultilineComment",
+            Self::MultilineDumpling => "MultilineDumpling",
+            Self::OrphanedDumpling => "OrphanedDumpling",
+            Self::OrphanedStick => "OrphanedStick",
+            Self::UndefinedLabel(_) => "UndefinedLabel",
+            Self::UnterminatedComment => "UnterminatedComment",
+            Self::UnterminatedDumpling => "UnterminatedDumpling",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct CompileError {
     pub kind: CompileErrorKind,
     pub line: usize,
     pub column: usize,
+    pub file: SourceId,
 }
 
 impl CompileError {
+    /// Makes an error in the first file, use `in_file` for errors in other files.
     pub fn new(kind: CompileErrorKind, line: usize, column: usize) -> Self {
         Self {
             kind,
             line,
             column,
+            file: SourceId::default(),
         }
     }
-}
-
-impl std::fmt::Debug for CompileErrorKind {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Display::fmt(&self, f)
-    }
-}
 
-impl std::fmt::Debug for CompileError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        std::fmt::Display::fmt(&self, f)
+    pub fn in_file(self, file: SourceId) -> Self {
+        Self { file, ..self }
     }
 }
 
@@ -70,12 +187,26 @@ impl std::fmt::Debug for CompileError {
 impl std::fmt::Display for CompileErrorKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::ComputedJumpInImport => write!(f, "jumps in imported files have to be to a line number or a label"),
             Self::CustomError(msg) => write!(f, "{}", msg),
+            Self::DuplicateLabel(label) => write!(f, "label `{}` is already declared", label),
+            Self::ExpectedCount(keyword) => write!(f, "expected number for `{}`", keyword),
+            Self::CyclicImport(path) => write!(f, "`{}` is already being imported, files cannot import each other in a cycle", path),
+            Self::ExpectedLabel => write!(f, "expected a name for `label`"),
+            Self::ExpectedPath => write!(f, "expected a path for `import`"),
+            Self::ImportFailed(path, reason) => write!(f, "could not import `{}`: {}", path, reason),
+            Self::ImportNotAlone => write!(f, "`import` has to be on a line of its own"),
+            Self::IntegerOverflow(literal) => write!(f, "integer literal `{}` does not fit in a 64-bit integer", literal),
+            Self::InvalidEscape(escape) => write!(f, "unknown escape sequence `{}`", escape),
             Self::InvalidToken(tok) => write!(f, "invalid token '{}'", tok),
-            Self::MultilineComment => write!(f, "due to complexities, multiline comments are not supported"),
+            Self::InvalidUnicodeEscape(escape) => write!(f, "`{}` is not a valid unicode escape, expected `\\u{{...}}` with 1 to 6 hex digits of a code point", escape),
+            Self::JumpOutOfRange(target, lines) => write!(f, "cannot jump to line {}, there are only lines 1 to {}", target, lines),
+            Self::MissingCount(keyword) => write!(f, "expected number for `{}`, found end of file", keyword),
+            Self::MultilineComment => write!(f, "comments inside of a dango cannot span multiple lines"),
             Self::MultilineDumpling => write!(f, "dumplings cannot span multiple lines"),
             Self::OrphanedDumpling => write!(f, "dumpling(s) must be connected to sticks"),
             Self::OrphanedStick => write!(f, "sticks must hold dango"),
+            Self::UndefinedLabel(label) => write!(f, "label `{}` is never declared", label),
             Self::UnterminatedComment => write!(f, "comments must be closed with a ':;'"),
             Self::UnterminatedDumpling => write!(f, "dumplings must be closed with a ')'"),
         }
@@ -92,29 +223,258 @@ impl std::fmt::Display for CompileError {
     }
 }
 
-#[derive(PartialEq)]
+impl Error for CompileError {}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum RuntimeError {
     CustomError(String),
+    DangoTooLong(usize, usize),
+    EmptySkewer,
+    // For natives whose failure comes from the host, like a parse error
+    Host(String, HostError),
     IncorrectOperationTypes(String),
+    InvalidArgument {
+        function: String,
+        position: usize,
+        expected: &'static str,
+        found: &'static str,
+    },
+    InvalidJump(&'static str),
+    Io(String, HostError),
+    JumpOutOfRange(i64, usize),
+    MemoryLimit,
+    NegativeRepeat(i64),
     NonexistentFunction(String),
+    NonexistentFunctions(Vec<String>),
     NotACodePoint(Option<i64>),
+    NotWritable(String),
+    NothingToResume,
+    OutOfFuel,
+    Reentrant(String),
+    SkewerTooShort(usize),
+    StackOverflow,
     StackUnderflow,
+    SystemTime,
     ZeroLengthDango,
 }
 
+impl RuntimeError {
+    /// An `Io` error saying what failed, caused by `err`.
+    pub fn io(message: impl Into<String>, err: std::io::Error) -> Self {
+        Self::Io(message.into(), HostError::new(err))
+    }
+
+    /// A `Host` error saying what failed, caused by `err`. Meant for natives that call into the host, so the
+    /// original error can still be found through `Error::source`.
+    pub fn host(message: impl Into<String>, err: impl Error + Send + Sync + 'static) -> Self {
+        Self::Host(message.into(), HostError::new(err))
+    }
+
+    /// A code that stays the same across releases, unlike the message, so tools can match on it.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::CustomError(_) => "D0101",
+            Self::DangoTooLong(..) => "D0113",
+            Self::EmptySkewer => "D0114",
+            Self::Host(..) => "D0122",
+            Self::IncorrectOperationTypes(_) => "D0102",
+            Self::InvalidArgument { .. } => "D0103",
+            Self::InvalidJump(_) => "D0115",
+            Self::Io(..) => "D0116",
+            Self::JumpOutOfRange(..) => "D0123",
+            Self::MemoryLimit => "D0104",
+            Self::NegativeRepeat(_) => "D0117",
+            Self::NonexistentFunction(_) => "D0105",
+            Self::NonexistentFunctions(_) => "D0106",
+            Self::NotACodePoint(_) => "D0107",
+            Self::NotWritable(_) => "D0118",
+            Self::NothingToResume => "D0108",
+            Self::OutOfFuel => "D0109",
+            Self::Reentrant(_) => "D0119",
+            Self::SkewerTooShort(_) => "D0120",
+            Self::StackOverflow => "D0110",
+            Self::StackUnderflow => "D0111",
+            Self::SystemTime => "D0121",
+            Self::ZeroLengthDango => "D0112",
+        }
+    }
+
+    /// The name of the variant, for tools that would rather not deal with codes.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CustomError(_) => "CustomError",
+            Self::DangoTooLong(..) => "DangoTooLong",
+            Self::EmptySkewer => "EmptySkewer",
+            Self::Host(..) => "Host",
+            Self::IncorrectOperationTypes(_) => "IncorrectOperationTypes",
+            Self::InvalidArgument { .. } => "InvalidArgument",
+            Self::InvalidJump(_) => "InvalidJump",
+            Self::Io(..) => "Io",
+            Self::JumpOutOfRange(..) => "JumpOutOfRange",
+            Self::MemoryLimit => "MemoryLimit",
+            Self::NegativeRepeat(_) => "NegativeRepeat",
+            Self::NonexistentFunction(_) => "NonexistentFunction",
+            Self::NonexistentFunctions(_) => "NonexistentFunctions",
+            Self::NotACodePoint(_) => "NotACodePoint",
+            Self::NotWritable(_) => "NotWritable",
+            Self::NothingToResume => "NothingToResume",
+            Self::OutOfFuel => "OutOfFuel",
+            Self::Reentrant(_) => "Reentrant",
+            Self::SkewerTooShort(_) => "SkewerTooShort",
+            Self::StackOverflow => "StackOverflow",
+            Self::StackUnderflow => "StackUnderflow",
+            Self::SystemTime => "SystemTime",
+            Self::ZeroLengthDango => "ZeroLengthDango",
+        }
+    }
+
+    /// The message without the `error: ` most messages start with.
+    pub fn message(&self) -> String {
+        let message = self.to_string();
+
+        match message.strip_prefix("error: ") {
+            Some(stripped) => stripped.to_string(),
+            None => message,
+        }
+    }
+}
+
 impl std::fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::CustomError(message) => write!(f, "{}", message),
+            Self::DangoTooLong(a, b) => write!(f, "error: cannot create new dango as the lengths ({} and {}) combined are too long", a, b),
+            Self::EmptySkewer => write!(f, "error: cannot have dango with zero dumplings, that's just a stick"),
+            Self::Host(message, _) => write!(f, "error: {}", message),
             Self::IncorrectOperationTypes(op) => write!(f, "error: incorrect {} types", op),
+            Self::InvalidArgument { function, position, expected, found } => if function.is_empty() {
+                write!(f, "error: argument {} must be {}, found {}", position, expected, found)
+            } else {
+                write!(f, "error: argument {} of `:{}` must be {}, found {}", position, function, expected, found)
+            }
+            Self::InvalidJump(found) => write!(f, "error: cannot jump with {}, only with an integer line number", found),
+            Self::Io(message, _) => write!(f, "error: {}", message),
+            Self::JumpOutOfRange(target, lines) => write!(f, "error: cannot jump to line {}, there are only lines 1 to {}", target, lines),
+            Self::MemoryLimit => write!(f, "error: the values on the stack would take up more memory than allowed"),
+            Self::NegativeRepeat(count) => write!(f, "error: cannot copy a string a negative number of times ({})", count),
             Self::NonexistentFunction(name) => write!(f, "error: function `:{}` does not exist", name),
+            Self::NonexistentFunctions(names) => write!(f, "error: functions {} do not exist", names.iter()
+                .map(|name| format!("`:{}`", name))
+                .collect::<Vec<String>>()
+                .join(", ")),
             Self::NotACodePoint(val) => if let Some(val) = val {
                 write!(f, "error: integer {} is not a code point", val)
             } else {
                 write!(f, "error: value is not a code point")
             }
+            Self::NotWritable(target) => write!(f, "error: cannot write to `{}`", target),
+            Self::NothingToResume => write!(f, "error: there is no paused program to resume"),
+            Self::OutOfFuel => write!(f, "error: the program ran out of fuel before it finished"),
+            Self::Reentrant(name) => write!(f, "error: native function `:{}` cannot be called while it is already running", name),
+            Self::SkewerTooShort(count) => write!(f, "error: skewer is too short for {} dumplings", count),
+            Self::StackOverflow => write!(f, "error: the stack cannot be pushed to when it is already full"),
             Self::StackUnderflow => write!(f, "error: the stack cannot be popped from when it is already empty"),
+            Self::SystemTime => write!(f, "error: could not get the system time"),
             Self::ZeroLengthDango => write!(f, "error: somehow, you have snuck a stick in here, but they don't taste good"),
         }
     }
 }
+
+impl Error for RuntimeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Host(_, err) | Self::Io(_, err) => Some(err.0.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// An error from outside of Dango that caused a `RuntimeError`. It is shared so that runtime errors can still be
+/// cloned, and compared by message since most errors can't be compared.
+#[derive(Clone)]
+pub struct HostError(Arc<dyn Error + Send + Sync>);
+
+impl HostError {
+    pub fn new(err: impl Error + Send + Sync + 'static) -> Self {
+        Self(Arc::new(err))
+    }
+
+    pub fn get(&self) -> &(dyn Error + Send + Sync + 'static) {
+        self.0.as_ref()
+    }
+}
+
+impl PartialEq for HostError {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl std::fmt::Debug for HostError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl std::fmt::Display for HostError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Where a dumpling is in the source code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceLocation {
+    pub line: usize,
+    pub column: usize,
+    pub file: SourceId,
+}
+
+impl SourceLocation {
+    /// Makes a location in the first file, use `in_file` for locations in other files.
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column, file: SourceId::default() }
+    }
+
+    pub fn in_file(self, file: SourceId) -> Self {
+        Self { file, ..self }
+    }
+}
+
+/// Where the runtime was when an error happened. Lines and dumplings start at 1, and dumplings are counted left
+/// to right like they are written, not in the order they run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeTrace {
+    /// The line of `file` that was running. Programs that weren't compiled from source only have their own lines.
+    pub line: usize,
+    pub file: SourceId,
+    pub dumpling: usize,
+    pub location: Option<SourceLocation>,
+    /// The native function that failed, if it was one.
+    pub function: Option<String>,
+    /// The last few jumps as the `(from, to)` lines of the source, oldest first. Their columns are always 1.
+    pub jumps: Vec<(SourceLocation, SourceLocation)>,
+}
+
+impl std::fmt::Display for RuntimeTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            Some(location) => write!(f, "  at {}:{}, dumpling {} of line {}", location.line, location.column, self.dumpling, self.line)?,
+            None => write!(f, "  at dumpling {} of line {}", self.dumpling, self.line)?,
+        }
+
+        if let Some(function) = &self.function {
+            write!(f, "\n  in `:{}`", function)?;
+        }
+
+        if !self.jumps.is_empty() {
+            let jumps = self.jumps.iter().rev()
+                .map(|(from, to)| format!("{} -> {}", from.line, to.line))
+                .collect::<Vec<String>>();
+
+            write!(f, "\n  recent jumps (latest first): {}", jumps.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
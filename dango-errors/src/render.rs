@@ -0,0 +1,258 @@
+//! Renders errors the way a person wants to read them, with the offending line of source and the dumpling
+//! underlined, instead of just a position.
+
+use std::fmt::Write;
+
+use super::{CompileError, DangoError, RuntimeError, RuntimeTrace, SourceLocation};
+use super::source_map::{SourceId, SourceMap};
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+pub struct Renderer<'a> {
+    // Indexed by `SourceId`, files only have a name when they come from a `SourceMap`
+    files: Vec<(Option<&'a str>, &'a str)>,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    /// `source` has to be the text that was compiled, otherwise the snippets won't line up. `color` adds ANSI
+    /// colors, which should only be used when printing to a terminal.
+    pub fn new(source: &'a str, color: bool) -> Self {
+        Self { files: vec![(None, source)], color }
+    }
+
+    /// Like `new`, but for programs made of several files. Positions are printed after the name of their file.
+    pub fn with_sources(sources: &'a SourceMap, color: bool) -> Self {
+        Self {
+            files: sources.files().map(|(_, name, source)| (Some(name), source)).collect(),
+            color,
+        }
+    }
+
+    pub fn render(&self, err: &DangoError) -> String {
+        match err {
+            DangoError::Compile(errors) => self.compile_errors(errors),
+            DangoError::Runtime(err, trace) => self.runtime_error(err, trace.as_deref()),
+        }
+    }
+
+    /// Renders every error, with errors on the same line sharing one snippet, and a count at the end if there
+    /// is more than one.
+    pub fn compile_errors(&self, errors: &[CompileError]) -> String {
+        let mut sorted = errors.iter().collect::<Vec<&CompileError>>();
+        // Errors at the end have no position and sort last
+        sorted.sort_by_key(|err| (err.file, err.line == 0, err.line, err.column));
+
+        let mut out = String::new();
+
+        for group in sorted.chunk_by(|a, b| (a.file, a.line) == (b.file, b.line)) {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+
+            for err in group {
+                self.header(&mut out, Some(err.kind.code()), &err.kind.to_string());
+            }
+
+            let (file, line) = (group[0].file, group[0].line);
+            if line == 0 {
+                self.arrow(&mut out, &self.position(file, "end of input"), self.gutter_width(line));
+                continue;
+            }
+
+            let columns = group.iter().map(|err| err.column).collect::<Vec<usize>>();
+            // One error explains itself, several need labels to tell their underlines apart
+            let labels = if group.len() > 1 {
+                group.iter().map(|err| err.kind.to_string()).collect()
+            } else {
+                vec![String::new()]
+            };
+
+            self.snippet(&mut out, file, line, &columns, &labels);
+        }
+
+        if errors.len() > 1 {
+            out.push('\n');
+            self.header(&mut out, None, &format!("could not compile due to {} errors", errors.len()));
+        }
+
+        out
+    }
+
+    pub fn runtime_error(&self, err: &RuntimeError, trace: Option<&RuntimeTrace>) -> String {
+        let mut out = String::new();
+        self.header(&mut out, Some(err.code()), &err.message());
+
+        let width = self.gutter_width(trace.and_then(|trace| trace.location).map_or(0, |location| location.line));
+
+        if let Some(trace) = trace {
+            self.trace(&mut out, trace, width);
+        }
+
+        let mut source = std::error::Error::source(err);
+        while let Some(cause) = source {
+            self.note(&mut out, &format!("caused by: {}", cause), width);
+            source = cause.source();
+        }
+
+        out
+    }
+
+    fn trace(&self, out: &mut String, trace: &RuntimeTrace, width: usize) {
+        match trace.location {
+            Some(location) => self.snippet(out, location.file, location.line, &[location.column], &[String::new()]),
+            None => self.arrow(out, &format!("dumpling {} of {}", trace.dumpling, self.line(trace.file, trace.line)), width),
+        }
+
+        if let Some(function) = &trace.function {
+            self.note(out, &format!("in `:{}`", function), width);
+        }
+
+        if !trace.jumps.is_empty() {
+            let line = |location: &SourceLocation| self.position(location.file, &location.line.to_string());
+            let jumps = trace.jumps.iter().rev()
+                .map(|(from, to)| format!("{} -> {}", line(from), line(to)))
+                .collect::<Vec<String>>();
+
+            self.note(out, &format!("recent jumps (latest first): {}", jumps.join(", ")), width);
+        }
+    }
+
+    // The code goes in the header so it can be looked up with `dango explain`
+    fn header(&self, out: &mut String, code: Option<&str>, message: &str) {
+        let error = match code {
+            Some(code) => format!("error[{}]", code),
+            None => "error".to_string(),
+        };
+
+        let _ = writeln!(out, "{}: {}", self.paint(RED, &error), self.paint(BOLD, message));
+    }
+
+    fn arrow(&self, out: &mut String, position: &str, width: usize) {
+        let _ = writeln!(out, "{:width$}{} {}", "", self.paint(BLUE, "-->"), position);
+    }
+
+    fn note(&self, out: &mut String, message: &str, width: usize) {
+        let _ = writeln!(out, "{:width$} {} {}", "", self.paint(BLUE, "="), message);
+    }
+
+    // Prints `line` with a caret under each of `columns`, followed by the matching label
+    fn snippet(&self, out: &mut String, file: SourceId, line: usize, columns: &[usize], labels: &[String]) {
+        let width = self.gutter_width(line);
+        let text = self.source(file).lines().nth(line - 1).unwrap_or("");
+        let bar = self.paint(BLUE, "|");
+
+        self.arrow(out, &self.position(file, &format!("{}:{}", line, columns[0])), width);
+        let _ = writeln!(out, "{:width$} {}", "", bar);
+        let _ = writeln!(out, "{} {} {}", self.paint(BLUE, &format!("{:>width$}", line)), bar, text);
+
+        for (column, label) in columns.iter().zip(labels) {
+            // Columns count characters, but wide ones take up two columns of the terminal
+            let padding = text.chars().take(column.saturating_sub(1)).map(char_width).sum::<usize>();
+            let dumpling = text.chars().skip(column.saturating_sub(1)).take(dumpling_width(text, *column))
+                .map(char_width)
+                .sum::<usize>();
+            let underline = "^".repeat(dumpling.max(1));
+
+            let _ = write!(out, "{:width$} {} {:padding$}{}", "", bar, "", self.paint(RED, &underline));
+            if label.is_empty() {
+                out.push('\n');
+            } else {
+                let _ = writeln!(out, " {}", self.paint(RED, label));
+            }
+        }
+    }
+
+    fn source(&self, file: SourceId) -> &str {
+        self.files.get(file.index()).map_or("", |(_, source)| source)
+    }
+
+    // Like `path/to/file.dango:12:5`, or just `12:5` if the file has no name
+    fn position(&self, file: SourceId, position: &str) -> String {
+        match self.files.get(file.index()) {
+            Some((Some(name), _)) => format!("{}:{}", name, position),
+            _ => position.to_string(),
+        }
+    }
+
+    // Like `path/to/file.dango:12`, or `line 12` if the file has no name
+    fn line(&self, file: SourceId, line: usize) -> String {
+        match self.files.get(file.index()) {
+            Some((Some(name), _)) => format!("{}:{}", name, line),
+            _ => format!("line {}", line),
+        }
+    }
+
+    fn gutter_width(&self, line: usize) -> usize {
+        line.max(1).to_string().len()
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// How many terminal columns `c` takes up. Wide characters take two and combining marks none, which covers what
+/// a line of Dango usually has in it without pulling in the full Unicode width tables.
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036f | 0x200b..=0x200f | 0xfe00..=0xfe0f => 0,
+        0x1100..=0x115f | 0x2e80..=0x303e | 0x3041..=0x33ff | 0x3400..=0x4dbf | 0x4e00..=0x9fff |
+            0xa000..=0xa4cf | 0xac00..=0xd7a3 | 0xf900..=0xfaff | 0xfe30..=0xfe4f | 0xff00..=0xff60 |
+            0xffe0..=0xffe6 | 0x1f300..=0x1f64f | 0x1f900..=0x1f9ff | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+// How many characters the dumpling, stick, word or escape starting at `column` takes up, so the whole of it can
+// be underlined. Unterminated dumplings run to the end of the line.
+pub(crate) fn dumpling_width(text: &str, column: usize) -> usize {
+    let mut chars = text.chars().skip(column.saturating_sub(1)).peekable();
+
+    let width = match chars.peek() {
+        Some('(') => match chars.clone().position(|c| c == ')') {
+            Some(end) => end + 1,
+            None => chars.count(),
+        }
+        Some('\\') => {
+            let escape = chars.clone().collect::<String>();
+
+            if escape.starts_with("\\(") {
+                escaped_dumpling_width(&escape)
+            } else if escape.starts_with("\\u{") {
+                escape.find('}').map_or(3, |end| escape[..=end].chars().count())
+            } else {
+                escape.chars().take(2).count()
+            }
+        }
+        Some('-') => chars.take_while(|c| *c == '-').count(),
+        Some(_) => chars.take_while(|c| !c.is_whitespace() && *c != '(').count(),
+        None => 0,
+    };
+
+    width.max(1)
+}
+
+// Like a dumpling, but `\)` doesn't close it
+fn escaped_dumpling_width(text: &str) -> usize {
+    let mut chars = text.chars().enumerate().skip(2);
+
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            ')' => return index + 1,
+            _ => (),
+        }
+    }
+
+    text.chars().count()
+}
@@ -0,0 +1,51 @@
+//! Keeps track of every file in a program, so errors can say which file they are in.
+
+/// Which file in a `SourceMap` something came from. The default is the first file, which is also the only one
+/// for programs that were compiled from a single string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourceId(usize);
+
+impl SourceId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+#[derive(Debug, Clone)]
+struct SourceFile {
+    name: String,
+    source: String,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file, where `name` is usually its path and is what errors print before the line and column.
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> SourceId {
+        self.files.push(SourceFile {
+            name: name.into(),
+            source: source.into(),
+        });
+
+        SourceId(self.files.len() - 1)
+    }
+
+    pub fn name(&self, id: SourceId) -> &str {
+        &self.files[id.0].name
+    }
+
+    pub fn source(&self, id: SourceId) -> &str {
+        &self.files[id.0].source
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = (SourceId, &str, &str)> {
+        self.files.iter().enumerate().map(|(id, file)| (SourceId(id), file.name.as_str(), file.source.as_str()))
+    }
+}
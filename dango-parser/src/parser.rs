@@ -1,6 +1,9 @@
 
+use std::collections::HashMap;
+
 use super::tokenizer::{Token, TokenKind};
-use dango_errors::{CompileError, CompileErrorKind};
+use dango_errors::{CompileError, CompileErrorKind, SourceLocation};
+use dango_errors::source_map::SourceId;
 use dango_runtime::instructions::*;
 
 // This function just checks if all dangos are attached to sticks.
@@ -12,7 +15,7 @@ fn validate_tokens(tokens: &Vec<Token>) -> Option<Vec<CompileError>> {
     for token in tokens {
         match token.kind {
             TokenKind::Add | TokenKind::CharCodePoint | TokenKind::Divide | TokenKind::Equal | TokenKind::Float(_) |
-                TokenKind::FunctionCall(_) | TokenKind::Greater | TokenKind::Int(_) | TokenKind::Jump | TokenKind::Length |
+                TokenKind::FunctionCall(_) | TokenKind::Greater | TokenKind::Int(_) | TokenKind::Jump | TokenKind::LabelRef(_) | TokenKind::Length |
                 TokenKind::Less | TokenKind::Multiply | TokenKind::NotEqual | TokenKind::Null | TokenKind::RawText(_) |
                 TokenKind::Stringify | TokenKind::Subtract | TokenKind::ToFloat | TokenKind::ToInt | TokenKind::While
                 => {
@@ -29,6 +32,8 @@ fn validate_tokens(tokens: &Vec<Token>) -> Option<Vec<CompileError>> {
                 last_is_dumpling = false;
                 errors.push(CompileError::new(CompileErrorKind::OrphanedDumpling, last_dumpling_position.0, last_dumpling_position.1));
             },
+            // Comments can go anywhere outside of dumplings, even between a dumpling and its stick
+            TokenKind::Comment => (),
             TokenKind::Eof => if last_is_dumpling {
                 errors.push(CompileError::new(CompileErrorKind::OrphanedDumpling, 0, 0));
                 break;
@@ -47,56 +52,137 @@ fn validate_tokens(tokens: &Vec<Token>) -> Option<Vec<CompileError>> {
     }
 }
 
+// Finds the line that every label is declared on, before anything is generated, so that jumps can go to labels
+// further down too.
+fn collect_labels(tokens: &[Token], errors: &mut Vec<CompileError>) -> HashMap<String, usize> {
+    let mut labels = HashMap::new();
+    let mut line = 1;
+
+    for token in tokens {
+        match &token.kind {
+            TokenKind::Newline => line += 1,
+            TokenKind::Label(name) if labels.insert(name.clone(), line).is_some() => {
+                errors.push(CompileError::new(CompileErrorKind::DuplicateLabel(name.clone()), token.line, token.column));
+            }
+            _ => (),
+        }
+    }
+
+    labels
+}
+
 // Surprisingly easier than I thought, the tokenization was the hardest part.
-pub fn parse(tokens: Vec<Token>) -> Result<Program, Vec<CompileError>> {
+pub fn parse(tokens: Vec<Token>, file: SourceId) -> Result<Program, Vec<CompileError>> {
     let mut prog = Program::new();
 
     let mut line = vec![];
 
     if let Some(errors) = validate_tokens(&tokens) {
-        return Err(errors);
+        return Err(errors.into_iter().map(|err| err.in_file(file)).collect());
     }
 
+    let mut errors = vec![];
+    let labels = collect_labels(&tokens, &mut errors);
+
+    let mut last_was_jump = false;
+
     for token in tokens {
-        match token.kind {
-            TokenKind::Add => line.push(Instruction::Add),
-            TokenKind::CharCodePoint => line.push(Instruction::CharFromCodePoint),
-            TokenKind::Divide => line.push(Instruction::Divide),
-            TokenKind::Equal => line.push(Instruction::Equal),
-            TokenKind::Eat => line.push(Instruction::Eat),
-            TokenKind::Fetch(count) => line.push(Instruction::Fetch(count)),
-            TokenKind::Float(val) => line.push(Instruction::Float(val)),
-            TokenKind::FunctionCall(name) => line.push(Instruction::FnCall(name)),
-            TokenKind::Greater => line.push(Instruction::Greater),
-            TokenKind::Int(val) => line.push(Instruction::Int(val)),
-            TokenKind::Jump => line.push(Instruction::Jump),
-            TokenKind::Length => line.push(Instruction::Length),
-            TokenKind::Less => line.push(Instruction::Less),
-            TokenKind::Multiply => line.push(Instruction::Multiply),
-            TokenKind::Null => line.push(Instruction::Null), // how did I forget this?
-            TokenKind::NotEqual => line.push(Instruction::NotEqual),
-            TokenKind::RawText(raw_text) => line.push(Instruction::Text(raw_text)),
-            TokenKind::Remove => line.push(Instruction::Remove),
-            TokenKind::Skewer(count) => line.push(Instruction::Skewer(count)),
-            TokenKind::Stringify => line.push(Instruction::Stringify),
-            TokenKind::Subtract => line.push(Instruction::Subtract),
-            TokenKind::ToFloat => line.push(Instruction::ToFloat),
-            TokenKind::ToInt => line.push(Instruction::ToInt),
-            TokenKind::While => line.push(Instruction::While),
+        let location = SourceLocation::new(token.line, token.column).in_file(file);
+        let after_jump = std::mem::replace(&mut last_was_jump, matches!(token.kind, TokenKind::Jump));
+
+        let instruction = match token.kind {
+            TokenKind::Add => Instruction::Add,
+            TokenKind::CharCodePoint => Instruction::CharFromCodePoint,
+            TokenKind::Divide => Instruction::Divide,
+            TokenKind::Equal => Instruction::Equal,
+            TokenKind::Eat => Instruction::Eat,
+            TokenKind::Fetch(count) => Instruction::Fetch(count),
+            TokenKind::Float(val) => Instruction::Float(val),
+            TokenKind::FunctionCall(name) => Instruction::FnCall(prog.intern(&name)),
+            TokenKind::Greater => Instruction::Greater,
+            TokenKind::Int(val) => Instruction::Int(val),
+            TokenKind::Import(path) => {
+                // Only `dango_utils::loader` knows where the file is, and it replaces imports before parsing
+                let reason = "imports only work in programs loaded from a file".to_string();
+                errors.push(CompileError::new(CompileErrorKind::ImportFailed(path, reason), token.line, token.column));
+                continue;
+            }
+            TokenKind::Jump => Instruction::Jump,
+            TokenKind::LabelRef(name) => match labels.get(&name) {
+                Some(line) => Instruction::Line(*line),
+                // Text like `(@user)` was already valid before labels, so it's only a typo when it gets jumped to
+                None if after_jump => {
+                    errors.push(CompileError::new(CompileErrorKind::UndefinedLabel(name), token.line, token.column));
+                    continue;
+                }
+                None => Instruction::Text(prog.intern(&format!("@{}", name))),
+            },
+            TokenKind::Length => Instruction::Length,
+            TokenKind::Less => Instruction::Less,
+            TokenKind::Multiply => Instruction::Multiply,
+            TokenKind::Null => Instruction::Null, // how did I forget this?
+            TokenKind::NotEqual => Instruction::NotEqual,
+            TokenKind::RawText(raw_text) => Instruction::Text(prog.intern(&raw_text)),
+            TokenKind::Remove => Instruction::Remove,
+            TokenKind::Skewer(count) => Instruction::Skewer(count),
+            TokenKind::Stringify => Instruction::Stringify,
+            TokenKind::Subtract => Instruction::Subtract,
+            TokenKind::ToFloat => Instruction::ToFloat,
+            TokenKind::ToInt => Instruction::ToInt,
+            TokenKind::While => Instruction::While,
 
             // These tokens were only for syntax and should be ignored during code generation
-            TokenKind::Comment | TokenKind::Stick => (),
+            TokenKind::Comment | TokenKind::Label(_) | TokenKind::Stick => continue,
 
             // Couldn't split a list by predicate so I decided to do this
             TokenKind::Newline | TokenKind::Eof => {
                 line.reverse();
-                prog.add_line(line.clone());
-                line.clear();
+                prog.add_located_line(std::mem::take(&mut line));
+                continue;
             }
-        }
+        };
+
+        line.push((instruction, location));
+    }
+
+    if !errors.is_empty() {
+        return Err(errors.into_iter().map(|err| err.in_file(file)).collect());
     }
 
     prog.add_line(vec![Instruction::Nop]);
 
+    // Every line of the source is a line of the program, and the `Nop` is the line after the last one
+    for line in 0..prog.lines() {
+        prog.set_line_location(line, SourceLocation::new(line + 1, 1).in_file(file));
+    }
+
     Ok(prog)
 }
+
+/// Checks every jump whose target is an integer literal, like `(j)(3)`, against the `lines` that can be jumped to.
+/// This is separate from `parse` since a program that gets appended to another one can jump to lines that
+/// come before it.
+pub fn validate_jumps(program: &Program, lines: usize) -> Result<(), Vec<CompileError>> {
+    let mut errors = vec![];
+
+    for line in 0..program.lines() {
+        // Lines run back to front, so the target is pushed right before the jump
+        for (index, pair) in program.get_line(line).windows(2).enumerate() {
+            let [Instruction::Int(target), Instruction::Jump] = *pair else {
+                continue;
+            };
+
+            if target < 1 || target as u64 > lines as u64 {
+                let location = program.location(line, index).unwrap_or(SourceLocation::new(0, 0));
+                let kind = CompileErrorKind::JumpOutOfRange(target, lines);
+                errors.push(CompileError::new(kind, location.line, location.column).in_file(location.file));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
@@ -4,16 +4,20 @@
 use std::str::Chars;
 
 use dango_errors::{CompileError, CompileErrorKind};
+use dango_errors::source_map::SourceId;
 
 #[derive(Debug, PartialEq)]
 pub enum SpanKind {
+    Comment,                // ;: Hello, world! :;
     Dumpling,               // (Hello, world!)
+    EscapedDumpling,        // \(Hello\) world!\n)
     Newline,
     NonDumpling,            // eat
     Stick,                  // ----
     Eof,
 }
 
+/// A span of source code. Columns start at 1 and count `char`s, diagnostics work out UTF-16 columns from these.
 #[derive(Debug)]
 pub struct SpanToken<'a> {
     pub kind: SpanKind,
@@ -28,7 +32,9 @@ impl<'a> SpanToken<'a> {
     }
 }
 
-pub fn tokenize_into_spans(source: &str) -> Result<Vec<SpanToken<'_>>, Vec<CompileError>> {
+/// Splits `source` into spans. Errors are reported in `file`, which is the default `SourceId` for programs that
+/// are a single string.
+pub fn tokenize_into_spans(source: &str, file: SourceId) -> Result<Vec<SpanToken<'_>>, Vec<CompileError>> {
     let mut tokenizer = SpanTokenizer {
         source: source,
         chars: source.chars(),
@@ -36,6 +42,10 @@ pub fn tokenize_into_spans(source: &str) -> Result<Vec<SpanToken<'_>>, Vec<Compi
         current_pos: Position::new(1, 1),
         start: 0,
         current: 0,
+
+        comment_start: None,
+        in_dango: false,
+        last_word: None,
     };
 
     let mut errors = vec![];
@@ -58,13 +68,13 @@ pub fn tokenize_into_spans(source: &str) -> Result<Vec<SpanToken<'_>>, Vec<Compi
             } else {
                 // we are 100% sure that span is an error
                 // so this unsafe is actually safe
-                errors.push(unsafe { span.unwrap_err_unchecked() });
+                errors.push(unsafe { span.unwrap_err_unchecked() }.in_file(file));
                 None
             }
         })
         .collect();
 
-    spans.push(SpanToken::new(SpanKind::Eof, "", tokenizer.start_pos.line, tokenizer.start_pos.column));
+    spans.push(tokenizer.span(SpanKind::Eof, ""));
 
     if errors.len() > 0 {
         Err(errors)
@@ -93,8 +103,16 @@ pub struct SpanTokenizer<'a> {
     start_pos: Position,
     current_pos: Position,
 
+    // Byte offsets into `source`, unlike the columns in the positions
     start: usize,
     current: usize,
+
+    // Where the comment that is still open started, since comments can go on for multiple lines
+    comment_start: Option<Position>,
+    // Whether the last span was part of a dango, so a stick or more instructions are still expected on this line
+    in_dango: bool,
+    // The text of the last span if it was a word, to tell the count after `fetch` and `skewer` apart
+    last_word: Option<&'a str>,
 }
 
 impl<'a> SpanTokenizer<'a> {
@@ -103,18 +121,50 @@ impl<'a> SpanTokenizer<'a> {
         self.start = self.current;
         self.start_pos = self.current_pos;
 
-        match self.first() {
+        // The rest of a comment from an earlier line
+        if self.comment_start.is_some() && self.first() != Some('\n') {
+            return self.consume_comment();
+        }
+
+        let span = match self.first() {
             Some(c) => match c {
                 '\n' => {
                     self.advance();
-                    Ok(SpanToken::new(SpanKind::Newline, "", self.start_pos.line, self.start_pos.column))
+                    Ok(self.span(SpanKind::Newline, ""))
                 },
-                '(' => self.consume_dumpling(),
+                '(' => self.consume_dumpling(false),
+                '\\' if self.matches_string("\\(") => {
+                    self.advance();
+                    self.consume_dumpling(true)
+                }
                 '-' => self.consume_stick(),
+                ';' if self.matches_string(";:") => {
+                    self.advance();
+                    self.advance();
+                    self.comment_start = Some(self.start_pos);
+                    self.consume_comment()
+                }
                 _ => self.consume_misc(),
             }
-            None => Ok(SpanToken::new(SpanKind::Eof, "", self.start_pos.line, self.start_pos.column)),
+            None => Ok(self.span(SpanKind::Eof, "")),
+        };
+
+        if let Ok(span) = &span && span.kind != SpanKind::Comment {
+            // Keywords are instructions too, so a comment after one can't end the line early either
+            self.in_dango = match span.kind {
+                SpanKind::Dumpling | SpanKind::EscapedDumpling => true,
+                SpanKind::NonDumpling => matches!(span.text, "eat" | "fetch" | "remove" | "skewer")
+                    || matches!(self.last_word, Some("fetch" | "skewer")),
+                _ => false,
+            };
+            self.last_word = (span.kind == SpanKind::NonDumpling).then_some(span.text);
         }
+
+        span
+    }
+
+    fn span(&self, kind: SpanKind, text: &'a str) -> SpanToken<'a> {
+        SpanToken::new(kind, text, self.start_pos.line, self.start_pos.column)
     }
 
     fn first(&self) -> Option<char> {
@@ -122,30 +172,20 @@ impl<'a> SpanTokenizer<'a> {
     }
     
     fn matches_string(&self, string: &str) -> bool {
-        let mut iter = self.chars.clone();
-        let mut iter_str = string.chars();
-        for _ in 0..string.len() {
-            if iter.next() != iter_str.next() { return false; }
-        }
-
-        true
+        self.chars.as_str().starts_with(string)
     }
 
     fn advance(&mut self) -> Option<char> {
-        if self.current > self.source.len() {
-            return None;
-        }
-
-        let c = self.chars.next();
+        let c = self.chars.next()?;
 
-        self.current += 1;
+        self.current += c.len_utf8();
         self.current_pos.column += 1;
 
-        if c == Some('\n') {
+        if c == '\n' {
             self.newline();
         }
 
-        c
+        Some(c)
     }
 
     fn newline(&mut self) {
@@ -162,7 +202,9 @@ impl<'a> SpanTokenizer<'a> {
     }
 
     // AAA WHY IS THIS SUCH A FUNNY NAME FOR A FUNCTION
-    fn consume_dumpling(&mut self) -> Result<SpanToken<'a>, CompileError> {
+    // Escaped dumplings start with a `\`, and `\)` doesn't close them. The escapes themselves are left to the
+    // tokenizer.
+    fn consume_dumpling(&mut self, escaped: bool) -> Result<SpanToken<'a>, CompileError> {
         while let Some(c) = self.first() {
             if c == '\n' {
                 return Err(CompileError::new(CompileErrorKind::MultilineDumpling, self.start_pos.line, self.start_pos.column));
@@ -170,6 +212,10 @@ impl<'a> SpanTokenizer<'a> {
             if c == ')' { break; }
 
             self.advance();
+
+            if escaped && c == '\\' && self.first().is_some_and(|c| c != '\n') {
+                self.advance();
+            }
         }
 
         if let Some(last) = self.first() && last == ')' {
@@ -178,7 +224,11 @@ impl<'a> SpanTokenizer<'a> {
             return Err(CompileError::new(CompileErrorKind::UnterminatedDumpling, self.start_pos.line, self.start_pos.column));
         }
 
-        Ok(SpanToken::new(SpanKind::Dumpling, &self.source[self.start + 1..self.current - 1], self.start_pos.line, self.start_pos.column))
+        if escaped {
+            Ok(self.span(SpanKind::EscapedDumpling, &self.source[self.start + 2..self.current - 1]))
+        } else {
+            Ok(self.span(SpanKind::Dumpling, &self.source[self.start + 1..self.current - 1]))
+        }
     }
 
     fn consume_stick(&mut self) -> Result<SpanToken<'a>, CompileError> {
@@ -191,13 +241,48 @@ impl<'a> SpanTokenizer<'a> {
         self.advance();
         self.advance();
         self.advance();
-        Ok(SpanToken::new(SpanKind::Stick, &self.source[self.start..self.current], self.start_pos.line, self.start_pos.column))
+        Ok(self.span(SpanKind::Stick, &self.source[self.start..self.current]))
+    }
+
+    // Comments stop at the end of the line, and `consume_span` picks them back up on the next one. That way
+    // the newlines in them are still tokenized and line numbers for jumps don't change.
+    fn consume_comment(&mut self) -> Result<SpanToken<'a>, CompileError> {
+        // `consume_span` always sets this before calling
+        let start = self.comment_start.unwrap_or(self.start_pos);
+
+        loop {
+            if self.matches_string(":;") {
+                self.advance();
+                self.advance();
+                self.comment_start = None;
+                break;
+            }
+
+            match self.first() {
+                Some('\n') => if self.in_dango {
+                    // The dango would end up split over two lines
+                    return Err(CompileError::new(CompileErrorKind::MultilineComment, start.line, start.column));
+                } else {
+                    break;
+                },
+                Some(_) => {
+                    self.advance();
+                }
+                None => {
+                    self.comment_start = None;
+                    return Err(CompileError::new(CompileErrorKind::UnterminatedComment, start.line, start.column));
+                }
+            }
+        }
+
+        Ok(self.span(SpanKind::Comment, &self.source[self.start..self.current]))
     }
 
     fn consume_misc(&mut self) -> Result<SpanToken<'a>, CompileError> {
         while let Some(c) = self.first() {
             match c {
                 '(' | '-' => break,
+                ';' if self.matches_string(";:") => break,
                 _ => (),
             }
             if c.is_whitespace() { break; }
@@ -205,6 +290,6 @@ impl<'a> SpanTokenizer<'a> {
             self.advance();
         }
 
-        Ok(SpanToken::new(SpanKind::NonDumpling, &self.source[self.start..self.current], self.start_pos.line, self.start_pos.column))
+        Ok(self.span(SpanKind::NonDumpling, &self.source[self.start..self.current]))
     }
 }
@@ -2,6 +2,7 @@
 
 use super::span_tokenizer::{SpanToken, SpanKind};
 use dango_errors::{CompileError, CompileErrorKind};
+use dango_errors::source_map::SourceId;
 
 #[derive(Debug)]
 pub enum TokenKind {
@@ -16,6 +17,7 @@ pub enum TokenKind {
     Greater,                    // (>)
     Int(i64),                   // (12345)
     Jump,                       // (j)
+    LabelRef(String),           // (@loop)
     Length,                     // (len)
     Less,                       // (<)
     Multiply,                   // (*)
@@ -30,11 +32,13 @@ pub enum TokenKind {
 
     // Misc
     Eat,                        // eat
+    Import(String),             // import lib.dango
+    Label(String),              // label loop
     Remove,                     // remove
     Skewer(u8),                 // skewer 5
 
     // More misc
-    Comment,
+    Comment,                    // ;: Hello, world! :;
     Eof,
     Newline,
     Stick,                      // ----
@@ -56,7 +60,7 @@ impl Token {
     }
 }
 
-pub fn tokenize(span_tokens: Vec<SpanToken<'_>>) -> Result<Vec<Token>, Vec<CompileError>> {
+pub fn tokenize(span_tokens: Vec<SpanToken<'_>>, file: SourceId) -> Result<Vec<Token>, Vec<CompileError>> {
     let mut tokenizer = Tokenizer {
         span_tokens,
         index: 0,
@@ -81,7 +85,7 @@ pub fn tokenize(span_tokens: Vec<SpanToken<'_>>) -> Result<Vec<Token>, Vec<Compi
             match tok {
                 Ok(tok) => Some(tok),
                 Err(err) => {
-                    errors.push(err);
+                    errors.push(err.in_file(file));
                     None
                 }
             }
@@ -107,7 +111,8 @@ impl<'a> Tokenizer<'a> {
     pub fn tokenize_span(&mut self) -> Result<Token, CompileError> {
         let tok = match self.current() {
             Some(span) => match span.kind {
-                SpanKind::Dumpling => self.tokenize_dumpling(),
+                SpanKind::Comment => Ok(Token::new(TokenKind::Comment, span.line, span.column)),
+                SpanKind::Dumpling | SpanKind::EscapedDumpling => self.tokenize_dumpling(),
                 SpanKind::NonDumpling => self.tokenize_operation(),
                 SpanKind::Newline => Ok(Token::new(TokenKind::Newline, span.line, span.column)),
                 SpanKind::Stick => Ok(Token::new(TokenKind::Stick, span.line, span.column)),
@@ -138,6 +143,12 @@ impl<'a> Tokenizer<'a> {
         // No need for if let because this function wouldn't have been called if there was no span
         let current = unsafe { self.current().unwrap_unchecked() };
 
+        // Escaped dumplings are always text, so `\(+)` is a plus sign and not an addition
+        if current.kind == SpanKind::EscapedDumpling {
+            let text = unescape(current.text, current.line, current.column + 2)?;
+            return Ok(Token::new(TokenKind::RawText(text), current.line, current.column));
+        }
+
         if current.text.len() == 0 { return Ok(Token::new(TokenKind::Null, current.line, current.column)); }
 
         if current.text.chars().next().unwrap() == ':' {
@@ -145,6 +156,11 @@ impl<'a> Tokenizer<'a> {
             return Ok(Token::new(TokenKind::FunctionCall(trimmed.to_string()), current.line, current.column));
         }
 
+        // `(@)` on its own is still text
+        if let Some(label) = current.text.strip_prefix('@') && !label.is_empty() {
+            return Ok(Token::new(TokenKind::LabelRef(label.to_string()), current.line, current.column));
+        }
+
         match current.text {
             "+" => return Ok(Token::new(TokenKind::Add, current.line, current.column)),
             "/" => return Ok(Token::new(TokenKind::Divide, current.line, current.column)),
@@ -164,12 +180,10 @@ impl<'a> Tokenizer<'a> {
             _ => (),
         }
 
-        if let Ok(as_int) = current.text.parse::<i64>() {
-            return Ok(Token::new(TokenKind::Int(as_int), current.line, current.column));
-        }
-
-        if let Ok(as_float) = current.text.parse::<f64>() {
-            return Ok(Token::new(TokenKind::Float(as_float), current.line, current.column));
+        if let Some(number) = parse_number(current.text) {
+            return number
+                .map(|kind| Token::new(kind, current.line, current.column))
+                .map_err(|kind| CompileError::new(kind, current.line, current.column));
         }
 
         Ok(Token::new(TokenKind::RawText(current.text.to_string()), current.line, current.column))
@@ -184,9 +198,9 @@ impl<'a> Tokenizer<'a> {
             "eat" => Ok(Token::new(TokenKind::Eat, current.line, current.column)),
             "fetch" => {
                 self.index += 1;
-                let Some(count) = self.current() else {
+                let Some(count) = self.current().filter(|count| count.kind != SpanKind::Eof) else {
                     return Err(CompileError::new(
-                        CompileErrorKind::CustomError("expected number, found end of file".to_string()),
+                        CompileErrorKind::MissingCount("fetch".to_string()),
                         current.line,
                         current.column
                     ));
@@ -194,7 +208,7 @@ impl<'a> Tokenizer<'a> {
 
                 if count.kind != SpanKind::NonDumpling {
                     return Err(CompileError::new(
-                        CompileErrorKind::CustomError("expected number for `fetch`".to_string()),
+                        CompileErrorKind::ExpectedCount("fetch".to_string()),
                         current.line,
                         current.column
                     ));
@@ -202,19 +216,38 @@ impl<'a> Tokenizer<'a> {
 
                 let Ok(count) = count.text.parse() else {
                     return Err(CompileError::new(
-                        CompileErrorKind::CustomError("expected number for `fetch`".to_string()),
+                        CompileErrorKind::ExpectedCount("fetch".to_string()),
                         current.line,
                         current.column
                     ));
                 };
                 Ok(Token::new(TokenKind::Fetch(count), current.line, current.column))
             }
+            "import" => {
+                self.index += 1;
+                match self.current() {
+                    // Paths with spaces or sticks in them can be written in a dumpling
+                    Some(path) if path.kind == SpanKind::NonDumpling || path.kind == SpanKind::Dumpling => {
+                        Ok(Token::new(TokenKind::Import(path.text.to_string()), current.line, current.column))
+                    }
+                    _ => Err(CompileError::new(CompileErrorKind::ExpectedPath, current.line, current.column)),
+                }
+            }
+            "label" => {
+                self.index += 1;
+                match self.current() {
+                    Some(name) if name.kind == SpanKind::NonDumpling => {
+                        Ok(Token::new(TokenKind::Label(name.text.to_string()), current.line, current.column))
+                    }
+                    _ => Err(CompileError::new(CompileErrorKind::ExpectedLabel, current.line, current.column)),
+                }
+            }
             "remove" => Ok(Token::new(TokenKind::Remove, current.line, current.column)),
             "skewer" => {
                 self.index += 1;
-                let Some(count) = self.current() else {
+                let Some(count) = self.current().filter(|count| count.kind != SpanKind::Eof) else {
                     return Err(CompileError::new(
-                        CompileErrorKind::CustomError("expected number, found end of file".to_string()),
+                        CompileErrorKind::MissingCount("skewer".to_string()),
                         current.line,
                         current.column
                     ));
@@ -222,7 +255,7 @@ impl<'a> Tokenizer<'a> {
 
                 if count.kind != SpanKind::NonDumpling {
                     return Err(CompileError::new(
-                        CompileErrorKind::CustomError("expected number for `skewer`".to_string()),
+                        CompileErrorKind::ExpectedCount("skewer".to_string()),
                         current.line,
                         current.column
                     ));
@@ -230,7 +263,7 @@ impl<'a> Tokenizer<'a> {
 
                 let Ok(count) = count.text.parse() else {
                     return Err(CompileError::new(
-                        CompileErrorKind::CustomError("expected number for `skewer`".to_string()),
+                        CompileErrorKind::ExpectedCount("skewer".to_string()),
                         current.line,
                         current.column
                     ));
@@ -241,3 +274,98 @@ impl<'a> Tokenizer<'a> {
         }
     }
 }
+
+// Parses integers in decimal, hexadecimal (`0x`), binary (`0b`) and octal (`0o`), and floats, all of which can
+// have underscores between digits. Returns `None` for anything that isn't a number, which is raw text instead.
+fn parse_number(text: &str) -> Option<Result<TokenKind, CompileErrorKind>> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+
+    let (radix, digits) = match unsigned.get(..2) {
+        Some("0x" | "0X") => (16, &unsigned[2..]),
+        Some("0b" | "0B") => (2, &unsigned[2..]),
+        Some("0o" | "0O") => (8, &unsigned[2..]),
+        _ => (10, unsigned),
+    };
+
+    // `1_000` is a number but `_1000` and `1__000` are text
+    let chars = digits.chars().collect::<Vec<char>>();
+    let separated = chars.iter().enumerate().all(|(index, c)| {
+        *c != '_' || (index > 0 && chars[index - 1].is_digit(radix) && chars.get(index + 1).is_some_and(|c| c.is_digit(radix)))
+    });
+    if !separated {
+        return None;
+    }
+
+    let digits = digits.replace('_', "");
+
+    if radix != 10 || digits.chars().all(|c| c.is_ascii_digit()) {
+        if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+
+        // Parsed as an `i128` so that `-0x8000000000000000` still fits once it's negated
+        let value = i128::from_str_radix(&digits, radix).ok()
+            .map(|value| if negative { -value } else { value })
+            .and_then(|value| i64::try_from(value).ok());
+
+        return Some(value.map(TokenKind::Int).ok_or(CompileErrorKind::IntegerOverflow(text.to_string())));
+    }
+
+    // Anything `f64` understands, like `1.5`, `2e-3` and `inf`
+    text.replace('_', "").parse::<f64>().ok().map(|value| Ok(TokenKind::Float(value)))
+}
+
+// Replaces the escapes in the text of an escaped dumpling, which starts at `column`
+fn unescape(text: &str, line: usize, column: usize) -> Result<String, CompileError> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars().enumerate().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        let error = |kind| CompileError::new(kind, line, column + index);
+
+        match chars.next().map(|(_, c)| c) {
+            Some('\\') => unescaped.push('\\'),
+            Some('(') => unescaped.push('('),
+            Some(')') => unescaped.push(')'),
+            Some('0') => unescaped.push('\0'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some('u') => {
+                // Everything up to the closing brace, so the error can show the whole escape
+                let mut escape = "\\u".to_string();
+                if chars.next_if(|(_, c)| *c == '{').is_some() {
+                    escape.push('{');
+
+                    while let Some((_, c)) = chars.next_if(|(_, c)| *c != '\\') {
+                        escape.push(c);
+                        if c == '}' { break; }
+                    }
+                }
+
+                let character = escape.strip_prefix("\\u{")
+                    .and_then(|rest| rest.strip_suffix('}'))
+                    .filter(|digits| (1..=6).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_hexdigit()))
+                    .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    .and_then(char::from_u32);
+
+                match character {
+                    Some(character) => unescaped.push(character),
+                    None => return Err(error(CompileErrorKind::InvalidUnicodeEscape(escape))),
+                }
+            }
+            Some(other) => return Err(error(CompileErrorKind::InvalidEscape(format!("\\{}", other)))),
+            None => return Err(error(CompileErrorKind::InvalidEscape("\\".to_string()))),
+        }
+    }
+
+    Ok(unescaped)
+}
@@ -0,0 +1,143 @@
+//! Conversions between `Value` and Rust types, so native functions don't have to match on every value they
+//! pop. See `Runtime::pop_as` and `native_fn!`.
+
+use dango_errors::RuntimeError;
+
+use super::Value;
+
+/// What every native function returns, spelled out for `native_fn!`.
+pub type NativeResult = Result<Value, RuntimeError>;
+
+pub trait FromValue: Sized {
+    /// What the value should have been, as in "argument 1 must be {EXPECTED}".
+    const EXPECTED: &'static str;
+
+    /// Gives the value back if it can't be converted, so that it can be put in the error.
+    fn from_value(value: Value) -> Result<Self, Value>;
+}
+
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl FromValue for Value {
+    const EXPECTED: &'static str = "a value";
+
+    fn from_value(value: Value) -> Result<Self, Value> {
+        Ok(value)
+    }
+}
+
+impl FromValue for i64 {
+    const EXPECTED: &'static str = "an integer";
+
+    fn from_value(value: Value) -> Result<Self, Value> {
+        match value {
+            Value::Int(val) => Ok(val),
+            _ => Err(value),
+        }
+    }
+}
+
+// Integers are accepted too, like every math function in the stdlib always has
+impl FromValue for f64 {
+    const EXPECTED: &'static str = "a number";
+
+    fn from_value(value: Value) -> Result<Self, Value> {
+        match value {
+            Value::Int(val) => Ok(val as f64),
+            Value::Float(val) => Ok(val),
+            _ => Err(value),
+        }
+    }
+}
+
+impl FromValue for String {
+    const EXPECTED: &'static str = "a string";
+
+    fn from_value(value: Value) -> Result<Self, Value> {
+        match value {
+            Value::String(val) => Ok(val),
+            _ => Err(value),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Nil
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Int(self)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+// Dango has no booleans, comparisons push 1 or 0 as well
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Int(self.into())
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Nil, IntoValue::into_value)
+    }
+}
+
+/// Turns a closure-like expression with typed arguments into a native function that can be passed to
+/// `Runtime::register_function`. The last argument is the top of the stack.
+///
+/// ```
+/// # use dango_runtime::{native_fn, runtime::Runtime};
+/// let mut runtime = Runtime::new();
+/// runtime.register_function("math-pow".to_string(), native_fn!(|base: f64, exp: f64| base.powf(exp)));
+/// ```
+#[macro_export]
+macro_rules! native_fn {
+    (|| $body:expr) => {
+        |_: &mut $crate::runtime::Runtime| -> $crate::convert::NativeResult {
+            Ok($crate::convert::IntoValue::into_value($body))
+        }
+    };
+    (|$($arg:ident: $ty:ty),+ $(,)?| $body:expr) => {
+        |runtime: &mut $crate::runtime::Runtime| -> $crate::convert::NativeResult {
+            $crate::native_fn!(@pop runtime, $($arg: $ty),+);
+            Ok($crate::convert::IntoValue::into_value($body))
+        }
+    };
+
+    // Arguments are popped last to first
+    (@pop $runtime:ident, $arg:ident: $ty:ty $(, $rest:ident: $rest_ty:ty)*) => {
+        $crate::native_fn!(@pop $runtime $(, $rest: $rest_ty)*);
+        let $arg = $runtime.pop_as::<$ty>()?;
+    };
+    (@pop $runtime:ident) => {};
+}
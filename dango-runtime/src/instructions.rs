@@ -1,22 +1,36 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+use dango_errors::SourceLocation;
+
+/// Index of a string interned in a `Program`.
+pub type StringId = usize;
+
+/// Index of a native function registered in a `Runtime`.
+pub type NativeId = usize;
+
+
+#[derive(Debug, Clone, Copy)]
 pub enum Instruction {
     Add,                          // (+)
     CharFromCodePoint,            // ('c)
     Divide,                       // (/)
     Equal,                        // (=)
     Float(f64),                   // (0.401)
-    FnCall(String),               // (:get-args)
+    FnCall(StringId),             // (:get-args)
     Greater,                      // (>)
     Int(i64),                     // (39)
     Jump,                         // (420)(j)
     Less,                         // (<)
     Length,                       // (len)
+    Line(usize),                  // (@loop), the line the label is on
     Multiply,                     // (*)
+    NativeCall(NativeId),         // (:get-args), after linking
     Nop,                          // needed for the interpreter to not crash out
     NotEqual,                     // (!=)
     Null,                         // ()
-    Text(String),                 // (Hello, world!)
+    Text(StringId),               // (Hello, world!)
     Stringify,                    // (')
     Subtract,                     // (-)
     ToFloat,                      // (;)
@@ -29,9 +43,21 @@ pub enum Instruction {
     Skewer(u8),
 }
 
+/// All lines are stored back to back in one vector, and `line_starts` holds the offset that each line begins
+/// at, so fetching an instruction (or jumping) is just an index. Text and function names are interned, which
+/// keeps `Instruction` small and `Copy`, so the runtime never has to clone one. Interned strings are shared, so
+/// pushing text only bumps a reference count.
 #[derive(Debug)]
 pub struct Program {
-    code: Vec<Vec<Instruction>>,
+    code: Vec<Instruction>,
+    // Where each instruction came from, for runtime errors
+    locations: Vec<Option<SourceLocation>>,
+    line_starts: Vec<usize>,
+    // Which file and line each line came from, since lines without any instructions can still be jumped to
+    line_locations: Vec<Option<SourceLocation>>,
+
+    strings: Vec<Arc<str>>,
+    string_ids: HashMap<Arc<str>, StringId>,
 }
 
 impl PartialEq for Program {
@@ -45,22 +71,148 @@ impl Program {
     pub fn new() -> Self {
         Self {
             code: vec![],
+            locations: vec![],
+            line_starts: vec![],
+            line_locations: vec![],
+
+            strings: vec![],
+            string_ids: HashMap::new(),
         }
     }
 
-    pub fn get_line(&self, line: usize) -> &Vec<Instruction> {
-        &self.code[line]
+    pub fn get_line(&self, line: usize) -> &[Instruction] {
+        &self.code[self.line_range(line)]
     }
 
-    pub fn get_line_mut(&mut self, line: usize) -> &mut Vec<Instruction> {
-        &mut self.code[line]
+    pub fn get_line_mut(&mut self, line: usize) -> &mut [Instruction] {
+        let range = self.line_range(line);
+        &mut self.code[range]
     }
 
     pub fn add_line(&mut self, line: Vec<Instruction>) {
-        self.code.push(line);
+        self.line_starts.push(self.code.len());
+        self.line_locations.push(None);
+        self.locations.resize(self.code.len() + line.len(), None);
+        self.code.extend(line);
+    }
+
+    /// Like `add_line`, but remembers where in the source each instruction came from.
+    pub fn add_located_line(&mut self, line: Vec<(Instruction, SourceLocation)>) {
+        self.line_starts.push(self.code.len());
+        self.line_locations.push(None);
+        for (instruction, location) in line {
+            self.code.push(instruction);
+            self.locations.push(Some(location));
+        }
+    }
+
+    pub fn pop_line(&mut self) -> Option<Vec<Instruction>> {
+        let start = self.line_starts.pop()?;
+        self.line_locations.pop();
+        self.locations.truncate(start);
+        Some(self.code.split_off(start))
+    }
+
+    /// Appends the lines of `other`, re-interning its strings since the two programs have separate tables.
+    pub fn extend(&mut self, other: Program) {
+        // Labels point at lines of `other`, which move down by however many lines are already here
+        let offset = self.lines();
+
+        for line in 0..other.lines() {
+            let instructions = other.get_line(line).iter()
+                .map(|instruction| match *instruction {
+                    Instruction::FnCall(id) => Instruction::FnCall(self.intern(other.string(id))),
+                    Instruction::Line(line) => Instruction::Line(line + offset),
+                    Instruction::Text(id) => Instruction::Text(self.intern(other.string(id))),
+                    instruction => instruction,
+                })
+                .collect::<Vec<Instruction>>();
+
+            let start = self.code.len();
+            self.add_line(instructions);
+            self.locations[start..].copy_from_slice(&other.locations[other.line_range(line)]);
+            self.line_locations.pop();
+            self.line_locations.push(other.line_locations[line]);
+        }
     }
 
     pub fn lines(&self) -> usize {
-        self.code.len()
+        self.line_starts.len()
+    }
+
+    /// The instruction at `index` in `line`, or `None` if either is out of range.
+    #[inline]
+    pub fn fetch(&self, line: usize, index: usize) -> Option<Instruction> {
+        let start = *self.line_starts.get(line)?;
+        let end = self.line_starts.get(line + 1).copied().unwrap_or(self.code.len());
+
+        if start + index < end {
+            Some(self.code[start + index])
+        } else {
+            None
+        }
+    }
+
+    /// Where the instruction at `index` in `line` was in the source, if the program was compiled from source.
+    pub fn location(&self, line: usize, index: usize) -> Option<SourceLocation> {
+        let range = self.line_range(line);
+        self.locations.get(range.start + index).copied().flatten()
+    }
+
+    /// Where `line` starts in the source, if it came from any. The column is always 1.
+    pub fn line_location(&self, line: usize) -> Option<SourceLocation> {
+        self.line_locations.get(line).copied().flatten()
+    }
+
+    pub fn set_line_location(&mut self, line: usize, location: SourceLocation) {
+        self.line_locations[line] = Some(location);
+    }
+
+    /// Returns the id of `string`, adding it to the table if it hasn't been seen before.
+    pub fn intern(&mut self, string: &str) -> StringId {
+        if let Some(id) = self.string_ids.get(string) {
+            return *id;
+        }
+
+        let id = self.strings.len();
+        let string = Arc::<str>::from(string);
+        self.strings.push(string.clone());
+        self.string_ids.insert(string, id);
+        id
+    }
+
+    pub fn string(&self, id: StringId) -> &str {
+        &self.strings[id]
+    }
+
+    /// Like `string`, but shares the string instead of borrowing it, so it can outlive the program.
+    pub fn shared_string(&self, id: StringId) -> Arc<str> {
+        self.strings[id].clone()
+    }
+
+    /// Replaces every `FnCall` that `resolve` finds a native for with a `NativeCall`. Returns the names that
+    /// couldn't be resolved, sorted and without duplicates.
+    pub fn resolve_calls(&mut self, mut resolve: impl FnMut(&str) -> Option<NativeId>) -> Vec<String> {
+        let mut unresolved = vec![];
+
+        for instruction in self.code.iter_mut() {
+            let Instruction::FnCall(name) = *instruction else { continue };
+
+            match resolve(&self.strings[name]) {
+                Some(id) => *instruction = Instruction::NativeCall(id),
+                None => unresolved.push(self.strings[name].to_string()),
+            }
+        }
+
+        unresolved.sort_unstable();
+        unresolved.dedup();
+        unresolved
+    }
+
+    fn line_range(&self, line: usize) -> Range<usize> {
+        let start = self.line_starts[line];
+        let end = self.line_starts.get(line + 1).copied().unwrap_or(self.code.len());
+
+        start..end
     }
 }
@@ -1,18 +1,26 @@
 
+pub mod convert;
 pub mod instructions;
 pub mod runtime;
+pub mod streams;
 
 #[cfg(not(feature = "exclude-stdlib"))]
 pub mod stdlib;
 
 use std::io::Write;
+use std::sync::Arc;
 
-use dango_errors::RuntimeError;
+use dango_errors::{RuntimeError, RuntimeTrace, SourceLocation};
 
-use instructions::{Instruction, Program};
-use runtime::Runtime;
+use convert::FromValue;
+use instructions::{Instruction, NativeId, Program};
+use runtime::{Native, Runtime};
 
 type NativeFn = fn(&mut Runtime) -> Result<Value, RuntimeError>;
+type NativeClosure = Box<dyn FnMut(&mut Runtime) -> Result<Value, RuntimeError> + Send>;
+
+// How many jumps a `RuntimeTrace` remembers
+const MAX_JUMPS: usize = 8;
 
 #[derive(Debug, Clone)]
 pub enum Value {
@@ -20,7 +28,7 @@ pub enum Value {
     Int(i64),               // i64
     Float(f64),             // f64
     String(String),         // stringified value
-    RawText(String),        // raw text
+    RawText(Arc<str>),      // raw text, shared with the program it came from
     Dango(Vec<Value>),      // array-like
 }
 
@@ -45,6 +53,32 @@ impl Value {
     }
 }
 
+impl Value {
+    /// What kind of value this is, for error messages.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::Nil => "nil",
+            Self::Int(_) => "an integer",
+            Self::Float(_) => "a float",
+            Self::String(_) => "a string",
+            Self::RawText(_) => "raw text",
+            Self::Dango(_) => "a dango",
+        }
+    }
+
+    /// Roughly how many bytes of heap memory this value holds onto, which is what the memory limit counts.
+    pub fn footprint(&self) -> usize {
+        match self {
+            Self::String(string) => string.len(),
+            Self::RawText(text) => text.len(),
+            Self::Dango(values) => values.iter()
+                .map(|value| std::mem::size_of::<Value>() + value.footprint())
+                .sum(),
+            _ => 0,
+        }
+    }
+}
+
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -144,48 +178,204 @@ impl std::fmt::Display for Value {
 }
 
 impl Runtime {
-    pub fn run(&mut self, program: Program) -> Result<Value, RuntimeError> {
+    pub fn run(&mut self, mut program: Program) -> Result<Value, RuntimeError> {
+        self.trace = None;
+        self.link(&mut program)?;
         self.program = program;
-        self.line = 0;
+        self.appended = false;
+        self.jumps.clear();
+        self.jump_to(0);
+        self.execute()?;
+
+        Ok(self.result())
+    }
+
+    /// Appends `program` to the loaded program and only runs the new lines, so jumps can still reach the
+    /// lines that were run before. Unlike `run`, the top of the stack is cloned instead of popped.
+    pub fn run_appended(&mut self, mut program: Program) -> Result<Value, RuntimeError> {
+        self.trace = None;
+        self.link(&mut program)?;
+
+        let start = self.program.lines();
+        self.program.extend(program);
+        self.appended = true;
+        self.jumps.clear();
+        self.jump_to(start);
+        self.execute()?;
+
+        Ok(self.result())
+    }
+
+    /// Picks up where the last `run` or `run_appended` left off after it returned `RuntimeError::OutOfFuel`.
+    /// Give the runtime more fuel first, or it will stop again straight away.
+    pub fn resume(&mut self) -> Result<Value, RuntimeError> {
+        self.trace = None;
+        if !self.paused {
+            return Err(RuntimeError::NothingToResume);
+        }
+
+        self.paused = false;
+        self.execute()?;
+
+        Ok(self.result())
+    }
+
+    /// Resolves every function call in `program` against the registered natives, so that calls don't look up
+    /// names while running and a missing function is reported before anything has run. `run` does this
+    /// itself, so this is only needed to check a program without running it.
+    pub fn link(&self, program: &mut Program) -> Result<(), RuntimeError> {
+        let mut unresolved = program.resolve_calls(|name| self.native_ids.get(name).copied());
+
+        match unresolved.len() {
+            0 => Ok(()),
+            1 => Err(RuntimeError::NonexistentFunction(unresolved.remove(0))),
+            _ => Err(RuntimeError::NonexistentFunctions(unresolved)),
+        }
+    }
+
+    fn jump_to(&mut self, line: usize) {
+        self.line = line;
+        self.index = 0;
         self.in_while = false;
+        self.paused = false;
+    }
+
+    fn execute(&mut self) -> Result<(), RuntimeError> {
+        let result = self.execute_lines();
+        if result.is_err() {
+            self.trace = Some(self.trace_here());
+        }
+
+        result
+    }
 
+    fn execute_lines(&mut self) -> Result<(), RuntimeError> {
         while self.line < self.program.lines() {
-            self.index = 0;
-            while self.index < self.program.get_line(self.line).len() {
-                // you better optimize this `clone` call.
-                self.run_inst(self.program.get_line(self.line)[self.index].clone())?;
+            // `fetch` also stops if `(while)` steps past the last line, which appended programs don't pad with a `Nop`
+            while let Some(instruction) = self.program.fetch(self.line, self.index) {
+                if let Some(fuel) = self.fuel.as_mut() {
+                    if *fuel == 0 {
+                        // `index` still points at this instruction, so `resume` can run it later
+                        self.paused = true;
+                        return Err(RuntimeError::OutOfFuel);
+                    }
+
+                    *fuel -= 1;
+                }
+
+                let index = self.index;
+                if let Err(err) = self.run_inst(instruction) {
+                    // `run_inst` has already moved on, so point back at the dumpling that failed
+                    self.index = index;
+                    return Err(err);
+                }
             }
 
             if !self.in_while {
                 self.line += 1;
             }
+            self.index = 0;
         }
 
-        if self.stack.len() > 0 {
-            Ok(self.stack.pop().unwrap())
+        Ok(())
+    }
+
+    /// Describes the dumpling at `line` and `index`, which is where execution stopped.
+    fn trace_here(&self) -> RuntimeTrace {
+        // Lines are stored reversed, so count the dumpling from the left like the source does
+        let length = self.program.get_line(self.line).len();
+        let function = match self.program.fetch(self.line, self.index) {
+            Some(Instruction::NativeCall(id)) => self.native_name(id).map(str::to_string),
+            _ => None,
+        };
+
+        // Imported files are linked after the file that imports them, so the lines of the program have to be
+        // looked up to say which file and line they are. Programs that weren't compiled have only their own lines
+        let source_line = |line: usize| self.program.line_location(line).unwrap_or(SourceLocation::new(line + 1, 1));
+        let here = source_line(self.line);
+
+        RuntimeTrace {
+            line: here.line,
+            file: here.file,
+            dumpling: length - self.index,
+            location: self.program.location(self.line, self.index),
+            function,
+            jumps: self.jumps.iter().map(|&(from, to)| (source_line(from - 1), source_line(to - 1))).collect(),
+        }
+    }
+
+    fn result(&mut self) -> Value {
+        if self.appended {
+            self.stack.last().cloned().unwrap_or(Value::Nil)
         } else {
-            Ok(Value::Nil)
+            self.pop().unwrap_or(Value::Nil)
         }
     }
-    
-    pub fn push(&mut self, value: Value) {
+
+    pub fn push(&mut self, value: Value) -> Result<(), RuntimeError> {
+        if self.max_stack.is_some_and(|max| self.stack.len() >= max) {
+            return Err(RuntimeError::StackOverflow);
+        }
+
+        if let Some(limit) = self.memory_limit {
+            let usage = self.memory_usage + value.footprint();
+            if usage > limit {
+                return Err(RuntimeError::MemoryLimit);
+            }
+            self.memory_usage = usage;
+        }
+
         self.stack.push(value);
+        Ok(())
     }
 
     pub fn pop(&mut self) -> Result<Value, RuntimeError> {
         match self.stack.pop() {
-            Some(value) => Ok(value),
+            Some(value) => {
+                self.arguments_popped += 1;
+
+                if self.memory_limit.is_some() {
+                    self.memory_usage -= value.footprint();
+                }
+                Ok(value)
+            }
             None => Err(RuntimeError::StackUnderflow),
         }
     }
 
-    pub fn peek(&self, depth: usize) -> Result<&Value, RuntimeError> {
-        match self.stack.get(self.stack.len() - depth - 1) {
-            Some(value) => Ok(value),
-            None => Err(RuntimeError::StackUnderflow),
+    /// Pops a value and converts it to `T`. Meant for native functions, since a value of the wrong type is
+    /// reported as an `InvalidArgument` naming the function and the argument.
+    pub fn pop_as<T: FromValue>(&mut self) -> Result<T, RuntimeError> {
+        T::from_value(self.pop()?).map_err(|value| self.argument_error(T::EXPECTED, &value))
+    }
+
+    /// The error for when the value a native function just popped isn't what it expected. Arguments are
+    /// counted from the top of the stack, which is also left to right from the function's dumpling.
+    pub fn argument_error(&self, expected: &'static str, found: &Value) -> RuntimeError {
+        RuntimeError::InvalidArgument {
+            function: self.native_call.and_then(|id| self.native_name(id)).unwrap_or_default().to_string(),
+            position: self.arguments_popped,
+            expected,
+            found: found.describe(),
         }
     }
 
+    pub fn peek(&self, depth: usize) -> Result<&Value, RuntimeError> {
+        // `checked_sub` because peeking into an empty stack used to underflow the index
+        let index = self.stack.len().checked_sub(depth + 1).ok_or(RuntimeError::StackUnderflow)?;
+
+        Ok(&self.stack[index])
+    }
+
+    pub fn stack_len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn clear_stack(&mut self) {
+        self.stack.clear();
+        self.memory_usage = 0;
+    }
+
     pub fn dump_stack(&self) {
         println!("---- stack ----");
         let mut i = 0;
@@ -196,6 +386,28 @@ impl Runtime {
         println!("---- stack end ----");
     }
 
+    fn call_native(&mut self, id: NativeId) -> Result<Value, RuntimeError> {
+        match &self.natives[id] {
+            Native::Fn(function) => function(self),
+            // The closure has to be taken out while it runs since it gets `self` mutably
+            Native::Closure(_) => {
+                let Native::Closure(mut closure) = std::mem::replace(&mut self.natives[id], Native::Running) else {
+                    unreachable!();
+                };
+
+                let val = closure(self);
+
+                // Unless the closure replaced itself in the meantime
+                if let Native::Running = self.natives[id] {
+                    self.natives[id] = Native::Closure(closure);
+                }
+
+                val
+            }
+            Native::Running => Err(RuntimeError::Reentrant(self.native_name(id).unwrap_or_default().to_string())),
+        }
+    }
+
     fn run_inst(&mut self, instruction: Instruction) -> Result<(), RuntimeError> {
         self.index += 1;
         match instruction {
@@ -204,18 +416,18 @@ impl Runtime {
                 let a = self.pop()?;
 
                 match (a, b) {
-                    (Value::Int(a), Value::Int(b)) => self.stack.push(Value::Int(a.wrapping_add(b))),
-                    (Value::Int(a), Value::Float(b)) => self.stack.push(Value::Float(a as f64 + b)),
-                    (Value::Float(a), Value::Int(b)) => self.stack.push(Value::Float(a + b as f64)),
-                    (Value::Float(a), Value::Float(b)) => self.stack.push(Value::Float(a + b)),
-                    (Value::String(a), Value::String(b)) => self.stack.push(Value::String(a + b.as_str())),
+                    (Value::Int(a), Value::Int(b)) => self.push(Value::Int(a.wrapping_add(b)))?,
+                    (Value::Int(a), Value::Float(b)) => self.push(Value::Float(a as f64 + b))?,
+                    (Value::Float(a), Value::Int(b)) => self.push(Value::Float(a + b as f64))?,
+                    (Value::Float(a), Value::Float(b)) => self.push(Value::Float(a + b))?,
+                    (Value::String(a), Value::String(b)) => self.push(Value::String(a + b.as_str()))?,
                     (Value::Dango(a), Value::Dango(b)) => {
                         if a.len() + b.len() > 5 {
-                            return Err(RuntimeError::CustomError(format!("cannot create new dango as the lengths ({} and {}) combined are too long", a.len(), b.len())));
+                            return Err(RuntimeError::DangoTooLong(a.len(), b.len()));
                         }
                         let mut vector = a;
                         vector.extend(b);
-                        self.stack.push(Value::dango_from_vec(vector));
+                        self.push(Value::dango_from_vec(vector))?;
                     }
                     _ => return Err(RuntimeError::IncorrectOperationTypes("addition".to_string())),
                 }
@@ -223,7 +435,7 @@ impl Runtime {
             Instruction::CharFromCodePoint => match self.pop()? {
                 Value::Int(codepoint) => match u32::try_from(codepoint) {
                     Ok(codepoint) => match char::from_u32(codepoint) {
-                        Some(character) => self.stack.push(Value::String(character.to_string())),
+                        Some(character) => self.push(Value::String(character.to_string()))?,
                         None => return Err(RuntimeError::NotACodePoint(Some(codepoint.into()))),
                     }
                     Err(_) => return Err(RuntimeError::NotACodePoint(Some(codepoint))),
@@ -240,42 +452,39 @@ impl Runtime {
                         // I wish Rust provided better syntax for `else` in `if let` where there's only two cases
                         // well buddy, have you ever heard of "match"?
                         match val {
-                            Ok(as_int) => self.stack.push(Value::Int(as_int)),
-                            Err(as_float) => self.stack.push(Value::Float(as_float)),
+                            Ok(as_int) => self.push(Value::Int(as_int))?,
+                            Err(as_float) => self.push(Value::Float(as_float))?,
                         }
                     }
                     _ => return Err(RuntimeError::IncorrectOperationTypes("division".to_string())),
                 }
             }
             Instruction::Eat => {
-                print!("{}", self.pop()?);
-                // Unlike in the CLI, we do want to lock `stdout` here because someone could be using Dango
-                // in a multi-threaded context.
-                std::io::stdout().lock().flush().unwrap();
+                let value = self.pop()?;
+
+                // Flushed right away so that output from `eat` and `:io-write` can't end up out of order
+                write!(self.stdout, "{}", value)
+                    .and_then(|_| self.stdout.flush())
+                    .map_err(|err| RuntimeError::io("`eat` failed to write to stdout", err))?;
             }
             Instruction::Equal => {
                 let b = self.pop()?;
                 let a = self.pop()?;
 
-                self.stack.push(Value::Int((a == b).into()));
-            }
-            Instruction::Fetch(depth) => self.stack.push(self.peek(depth)?.clone()),
-            Instruction::Float(val) => self.stack.push(Value::Float(val)),
-            Instruction::FnCall(name) => {
-                let Some(function) = self.natives.get(&name) else {
-                    return Err(RuntimeError::NonexistentFunction(name.to_owned()));
-                };
-
-                let val = function(self)?;
-                self.stack.push(val);
+                self.push(Value::Int((a == b).into()))?;
             }
+            Instruction::Fetch(depth) => self.push(self.peek(depth)?.clone())?,
+            Instruction::Float(val) => self.push(Value::Float(val))?,
+            // Only reachable if the program was never linked, since `link` fails on calls it can't resolve
+            Instruction::FnCall(name) => return Err(RuntimeError::NonexistentFunction(self.program.string(name).to_string())),
             Instruction::Greater => {
                 let b = self.pop()?;
                 let a = self.pop()?;
 
-                self.stack.push(Value::Int((a > b).into()));
+                self.push(Value::Int((a > b).into()))?;
             }
-            Instruction::Int(value) => self.stack.push(Value::Int(value)),
+            Instruction::Int(value) => self.push(Value::Int(value))?,
+            Instruction::Line(line) => self.push(Value::Int(line as i64))?,
             Instruction::Jump => {
                 let offset = self.pop()?;
 
@@ -283,57 +492,86 @@ impl Runtime {
 
                 match offset {
                     Value::Int(offset) => {
+                        // The line after the last one ends the program. `run` programs end with a `Nop` line for
+                        // that, appended ones don't, so they can jump one line further
+                        let last = self.program.lines() + self.appended as usize;
+                        if offset < 1 || offset as u64 > last as u64 {
+                            return Err(RuntimeError::JumpOutOfRange(offset, last));
+                        }
+
+                        if self.jumps.len() == MAX_JUMPS {
+                            self.jumps.pop_front();
+                        }
+                        self.jumps.push_back((self.line + 1, offset as usize));
+
                         self.line = offset as usize - 1; // line numbers start at 1 but indices start at 0
                         self.index = 0;
                         return Ok(());
                     }
-                    _ => return Err(RuntimeError::CustomError("cannot jump with a non-integer offset".to_string())),
+                    _ => return Err(RuntimeError::InvalidJump(offset.describe())),
                 }
             }
             // BREAKING CHANGE: `(len)` on an empty stack now throws a stack overflow error instead
             //                  of returning zero
             Instruction::Length => match self.peek(0)? {
-                Value::Dango(dango) => self.push(Value::Int(dango.len() as i64)),
-                _ => self.push(Value::Int(0)),
+                Value::Dango(dango) => self.push(Value::Int(dango.len() as i64))?,
+                _ => self.push(Value::Int(0))?,
             }
             Instruction::Less => {
                 let b = self.pop()?;
                 let a = self.pop()?;
 
-                self.stack.push(Value::Int((a < b).into()));
+                self.push(Value::Int((a < b).into()))?;
             }
             Instruction::Multiply => {
                 let b = self.pop()?;
                 let a = self.pop()?;
 
                 match (a, b) {
-                    (Value::Int(a), Value::Int(b)) => self.stack.push(Value::Int(a.wrapping_mul(b))),
-                    (Value::Int(a), Value::Float(b)) => self.stack.push(Value::Float(a as f64 * b)),
-                    (Value::Float(a), Value::Int(b)) => self.stack.push(Value::Float(a * b as f64)),
-                    (Value::Float(a), Value::Float(b)) => self.stack.push(Value::Float(a * b)),
+                    (Value::Int(a), Value::Int(b)) => self.push(Value::Int(a.wrapping_mul(b)))?,
+                    (Value::Int(a), Value::Float(b)) => self.push(Value::Float(a as f64 * b))?,
+                    (Value::Float(a), Value::Int(b)) => self.push(Value::Float(a * b as f64))?,
+                    (Value::Float(a), Value::Float(b)) => self.push(Value::Float(a * b))?,
                     (Value::String(a), Value::Int(b)) => match b {
-                        i64::MIN..0 => return Err(RuntimeError::CustomError("cannot copy a string a negative number of times".to_string())),
-                        0 => self.stack.push(Value::String(String::new())),
-                        1 => self.stack.push(Value::String(a)),
+                        i64::MIN..0 => return Err(RuntimeError::NegativeRepeat(b)),
+                        0 => self.push(Value::String(String::new()))?,
+                        1 => self.push(Value::String(a))?,
                         _ => {
-                            let mut value = a.clone();
-                            for _ in 1..=b {
-                                value.push_str(a.as_str());
+                            // The string is copied `b` more times on top of the original
+                            let copies = usize::try_from(b).ok().and_then(|b| b.checked_add(1));
+
+                            // Check before allocating, otherwise `push` would only find out after the damage is done
+                            let len = copies.and_then(|copies| a.len().checked_mul(copies));
+                            if self.memory_limit.is_some_and(|limit| len.is_none_or(|len| self.memory_usage + len > limit)) {
+                                return Err(RuntimeError::MemoryLimit);
                             }
-                            self.stack.push(Value::String(value));
+
+                            self.push(Value::String(a.repeat(b as usize + 1)))?;
                         }
                     }
                     _ => return Err(RuntimeError::IncorrectOperationTypes("multiplication".to_string())),
                 }
             }
+            Instruction::NativeCall(id) => {
+                // Remembered so that `pop_as` can say which function got a bad argument
+                let caller = self.native_call.replace(id);
+                let popped = std::mem::take(&mut self.arguments_popped);
+
+                let val = self.call_native(id);
+
+                self.native_call = caller;
+                self.arguments_popped = popped;
+
+                self.push(val?)?;
+            }
             Instruction::Nop => {}
             Instruction::NotEqual => {
                 let b = self.pop()?;
                 let a = self.pop()?;
 
-                self.stack.push(Value::Int((a != b).into()));
+                self.push(Value::Int((a != b).into()))?;
             }
-            Instruction::Null => self.stack.push(Value::Nil),
+            Instruction::Null => self.push(Value::Nil)?,
             Instruction::Remove => match self.pop()? {
                 Value::Dango(mut dango) => {
                     
@@ -344,14 +582,14 @@ impl Runtime {
                     dango.reverse();
 
                     if dango.len() > 0 {
-                        self.stack.push(Value::Dango(dango));
+                        self.push(Value::Dango(dango))?;
                     }
-                    self.stack.push(value);
+                    self.push(value)?;
                 }
                 _ => (),
             }
             Instruction::Skewer(count) => match count {
-                0 => return Err(RuntimeError::CustomError("cannot have dango with zero dumplings, that's just a stick".to_string())),
+                0 => return Err(RuntimeError::EmptySkewer),
                 1..=5 => {
                     let mut values = vec![];
 
@@ -359,9 +597,9 @@ impl Runtime {
                         values.push(self.pop()?);
                     }
 
-                    self.stack.push(Value::Dango(values));
+                    self.push(Value::Dango(values))?;
                 }
-                _ => return Err(RuntimeError::CustomError(format!("skewer is too short for {} dumplings", count))),
+                _ => return Err(RuntimeError::SkewerTooShort(count.into())),
             }
             Instruction::Subtract => {
                 let b = self.pop()?;
@@ -369,35 +607,35 @@ impl Runtime {
 
                 match (a, b) {
                     // Numeric types
-                    (Value::Int(a), Value::Int(b)) => self.stack.push(Value::Int(a.wrapping_sub(b))),
-                    (Value::Int(a), Value::Float(b)) => self.stack.push(Value::Float(a as f64 - b)),
-                    (Value::Float(a), Value::Int(b)) => self.stack.push(Value::Float(a - b as f64)),
-                    (Value::Float(a), Value::Float(b)) => self.stack.push(Value::Float(a - b)),
+                    (Value::Int(a), Value::Int(b)) => self.push(Value::Int(a.wrapping_sub(b)))?,
+                    (Value::Int(a), Value::Float(b)) => self.push(Value::Float(a as f64 - b))?,
+                    (Value::Float(a), Value::Int(b)) => self.push(Value::Float(a - b as f64))?,
+                    (Value::Float(a), Value::Float(b)) => self.push(Value::Float(a - b))?,
                     _ => return Err(RuntimeError::IncorrectOperationTypes("subtraction".to_string())),
                 }
             }
             Instruction::Stringify => match self.pop()? {
-                Value::RawText(text) => self.stack.push(Value::String(text)),
-                value => self.stack.push(Value::String(value.to_string())),
+                Value::RawText(text) => self.push(Value::String(text.to_string()))?,
+                value => self.push(Value::String(value.to_string()))?,
             }
-            Instruction::Text(text) => self.stack.push(Value::RawText(text)),
+            Instruction::Text(text) => self.push(Value::RawText(self.program.shared_string(text)))?,
             Instruction::ToFloat => match self.pop()? {
-                Value::Int(x) => self.push(Value::Float(x as f64)),
-                Value::Float(x) => self.push(Value::Float(x)),
+                Value::Int(x) => self.push(Value::Float(x as f64))?,
+                Value::Float(x) => self.push(Value::Float(x))?,
                 Value::String(string) => match string.parse::<f64>() {
-                    Ok(value) => self.push(Value::Float(value)),
-                    Err(_) => self.push(Value::Nil),
+                    Ok(value) => self.push(Value::Float(value))?,
+                    Err(_) => self.push(Value::Nil)?,
                 }
-                _ => self.push(Value::Nil),
+                _ => self.push(Value::Nil)?,
             }
             Instruction::ToInt => match self.pop()? {
-                Value::Int(x) => self.push(Value::Int(x)),
-                Value::Float(x) => self.push(Value::Int(x as i64)),
+                Value::Int(x) => self.push(Value::Int(x))?,
+                Value::Float(x) => self.push(Value::Int(x as i64))?,
                 Value::String(string) => match string.parse::<i64>() {
-                    Ok(value) => self.push(Value::Int(value)),
-                    Err(_) => self.push(Value::Nil),
+                    Ok(value) => self.push(Value::Int(value))?,
+                    Err(_) => self.push(Value::Nil)?,
                 }
-                _ => self.push(Value::Nil),
+                _ => self.push(Value::Nil)?,
             }
             Instruction::While => {
                 let condition = self.pop()?;
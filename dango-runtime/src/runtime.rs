@@ -1,9 +1,19 @@
 
-use std::collections::HashMap;
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, Write};
 
-use super::{NativeFn, Value};
+use dango_errors::{RuntimeError, RuntimeTrace};
+
+use super::{NativeClosure, NativeFn, Value};
 use super::instructions::*;
 
+pub(crate) enum Native {
+    Fn(NativeFn),
+    Closure(NativeClosure),
+    Running,                    // a closure that has been taken out to be called
+}
+
 pub struct Runtime {
     pub(crate) program: Program,
     pub(crate) line: usize,
@@ -11,8 +21,35 @@ pub struct Runtime {
 
     pub(crate) in_while: bool,
 
-    pub(crate) natives: HashMap<String, NativeFn>,
+    // Whether the program was run with `run_appended`, which changes how the result is returned
+    pub(crate) appended: bool,
+    pub(crate) paused: bool,
+    pub(crate) fuel: Option<u64>,
+
+    pub(crate) max_stack: Option<usize>,
+    // Only kept up to date while there is a memory limit
+    pub(crate) memory_limit: Option<usize>,
+    pub(crate) memory_usage: usize,
+
+    // Linked programs call natives by their index, so functions are never removed, only replaced
+    pub(crate) natives: Vec<Native>,
+    pub(crate) native_ids: HashMap<String, NativeId>,
+    // The native function being called and how many values it has popped so far
+    pub(crate) native_call: Option<NativeId>,
+    pub(crate) arguments_popped: usize,
     pub(crate) stack: Vec<Value>,
+
+    // Where the last run stopped with an error, and the most recent jumps as 1-based `(from, to)` lines
+    pub(crate) trace: Option<RuntimeTrace>,
+    pub(crate) jumps: VecDeque<(usize, usize)>,
+
+    // Host state for natives, one value per type
+    pub(crate) data: HashMap<TypeId, Box<dyn Any + Send>>,
+
+    // `None` reads from the process's stdin, which is locked per read so the CLI can still use it in between
+    pub(crate) stdin: Option<Box<dyn BufRead + Send>>,
+    pub(crate) stdout: Box<dyn Write + Send>,
+    pub(crate) stderr: Box<dyn Write + Send>,
 }
 
 impl Runtime {
@@ -24,12 +61,167 @@ impl Runtime {
 
             in_while: false,
 
-            natives: HashMap::new(),
+            appended: false,
+            paused: false,
+            fuel: None,
+
+            max_stack: None,
+            memory_limit: None,
+            memory_usage: 0,
+
+            natives: vec![],
+            native_ids: HashMap::new(),
+            native_call: None,
+            arguments_popped: 0,
             stack: vec![],
+
+            trace: None,
+            jumps: VecDeque::new(),
+
+            data: HashMap::new(),
+
+            stdin: None,
+            stdout: Box::new(std::io::stdout()),
+            stderr: Box::new(std::io::stderr()),
         }
     }
 
     pub fn register_function(&mut self, name: String, func: NativeFn) {
-        self.natives.insert(name, func);
+        self.register_native(name, Native::Fn(func));
+    }
+
+    /// Like `register_function`, but for closures, which can hold on to host state like counters, handles or
+    /// channels. Closures have to be `Send` so that the runtime stays `Send`.
+    pub fn register_closure<F>(&mut self, name: String, func: F)
+    where
+        F: FnMut(&mut Runtime) -> Result<Value, RuntimeError> + Send + 'static,
+    {
+        self.register_native(name, Native::Closure(Box::new(func)));
+    }
+
+    fn register_native(&mut self, name: String, native: Native) {
+        match self.native_ids.get(&name) {
+            Some(id) => self.natives[*id] = native,
+            None => {
+                self.native_ids.insert(name, self.natives.len());
+                self.natives.push(native);
+            }
+        }
+    }
+
+    /// Stores a value that natives can get back with `data` or `data_mut`. There is one slot per type, so this
+    /// returns the value of the same type that was there before, if any.
+    pub fn insert_data<T: Any + Send>(&mut self, value: T) -> Option<T> {
+        self.data.insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|old| old.downcast::<T>().ok())
+            .map(|old| *old)
+    }
+
+    pub fn data<T: Any + Send>(&self) -> Option<&T> {
+        self.data.get(&TypeId::of::<T>())?.downcast_ref::<T>()
+    }
+
+    pub fn data_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.data.get_mut(&TypeId::of::<T>())?.downcast_mut::<T>()
+    }
+
+    pub fn remove_data<T: Any + Send>(&mut self) -> Option<T> {
+        self.data.remove(&TypeId::of::<T>())
+            .and_then(|old| old.downcast::<T>().ok())
+            .map(|old| *old)
+    }
+
+    pub(crate) fn native_name(&self, id: NativeId) -> Option<&str> {
+        self.native_ids.iter()
+            .find(|(_, native_id)| **native_id == id)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Limits how many more instructions can run before `run` returns `RuntimeError::OutOfFuel`. `None`, the
+    /// default, means no limit.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Tops up the instruction budget, usually followed by `resume`. Does nothing if there is no limit.
+    pub fn add_fuel(&mut self, fuel: u64) {
+        if let Some(current) = self.fuel.as_mut() {
+            *current = current.saturating_add(fuel);
+        }
+    }
+
+    /// Whether the program ran out of fuel and can be continued with `resume`.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Limits how many values the stack can hold before pushing returns `RuntimeError::StackOverflow`.
+    pub fn set_max_stack(&mut self, max_stack: Option<usize>) {
+        self.max_stack = max_stack;
+    }
+
+    /// Limits how many bytes the strings and dango on the stack can take up in total (see `Value::footprint`)
+    /// before pushing returns `RuntimeError::MemoryLimit`.
+    pub fn set_memory_limit(&mut self, memory_limit: Option<usize>) {
+        self.memory_limit = memory_limit;
+        self.memory_usage = self.stack.iter().map(Value::footprint).sum();
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.stack.iter().map(Value::footprint).sum()
+    }
+
+    /// Redirects `eat` and `:io-write` to `stdout`, which is the process's standard output by default. Returns
+    /// the previous writer. Use `streams::SharedBuffer` to read the output back afterwards.
+    pub fn set_stdout(&mut self, stdout: Box<dyn Write + Send>) -> Box<dyn Write + Send> {
+        std::mem::replace(&mut self.stdout, stdout)
+    }
+
+    /// Like `set_stdout`, but for what `:io-write` sends to `stderr`.
+    pub fn set_stderr(&mut self, stderr: Box<dyn Write + Send>) -> Box<dyn Write + Send> {
+        std::mem::replace(&mut self.stderr, stderr)
+    }
+
+    /// Makes `:io-input` read from `stdin` instead of the process's standard input. Returns the previous reader,
+    /// or `None` if it was the process's standard input.
+    pub fn set_stdin(&mut self, stdin: Box<dyn BufRead + Send>) -> Option<Box<dyn BufRead + Send>> {
+        self.stdin.replace(stdin)
+    }
+
+    /// Reads a line (including the line ending) from the runtime's input, returning 0 at the end of the input.
+    pub fn read_line(&mut self, buf: &mut String) -> std::io::Result<usize> {
+        match self.stdin.as_mut() {
+            Some(stdin) => stdin.read_line(buf),
+            None => std::io::stdin().lock().read_line(buf),
+        }
+    }
+
+    pub fn stdout(&mut self) -> &mut dyn Write {
+        self.stdout.as_mut()
+    }
+
+    pub fn stderr(&mut self) -> &mut dyn Write {
+        self.stderr.as_mut()
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Where the last `run`, `run_appended` or `resume` stopped with an error. Errors found before anything
+    /// ran, like a missing function, have no trace.
+    pub fn trace(&self) -> Option<&RuntimeTrace> {
+        self.trace.as_ref()
+    }
+
+    /// The names of every registered native function, sorted so they can be listed nicely.
+    pub fn function_names(&self) -> Vec<&str> {
+        let mut names = self.native_ids.keys().map(String::as_str).collect::<Vec<&str>>();
+        names.sort_unstable();
+        names
     }
 }
@@ -0,0 +1,44 @@
+//! Helpers for redirecting a runtime's output, see `Runtime::set_stdout`.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// An in-memory writer that can be cloned, so one clone can be handed to the runtime while the other reads
+/// what was written.
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far, with invalid UTF-8 replaced.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.lock()).into_owned()
+    }
+
+    /// Empties the buffer and returns what was in it.
+    pub fn take(&self) -> String {
+        let bytes = std::mem::take(&mut *self.lock());
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<u8>> {
+        // A panic while writing can't leave a `Vec<u8>` in a bad state, so poisoning doesn't matter here
+        self.buffer.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.lock().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -1,9 +1,20 @@
 
+pub mod loader;
+
 use dango_errors::*;
+use dango_errors::source_map::SourceId;
 use dango_runtime::{Value, instructions::Program, runtime::Runtime};
 
 pub fn compile_str(source: &str) -> Result<Program, Vec<CompileError>> {
-    let span_tokens = dango_parser::span_tokenizer::tokenize_into_spans(source);
+    let program = compile_unchecked(source, SourceId::default())?;
+    dango_parser::parser::validate_jumps(&program, program.lines())?;
+
+    Ok(program)
+}
+
+// Compiles without checking jump targets, since those depend on where the program ends up
+fn compile_unchecked(source: &str, file: SourceId) -> Result<Program, Vec<CompileError>> {
+    let span_tokens = dango_parser::span_tokenizer::tokenize_into_spans(source, file);
 
     if let Err(errors) = span_tokens {
         return Err(errors);
@@ -11,13 +22,13 @@ pub fn compile_str(source: &str) -> Result<Program, Vec<CompileError>> {
 
     let span_tokens = unsafe { span_tokens.unwrap_unchecked() };
 
-    let res_tokens = dango_parser::tokenizer::tokenize(span_tokens);
+    let res_tokens = dango_parser::tokenizer::tokenize(span_tokens, file);
 
     let Ok(tokens) = res_tokens else {
         return unsafe { Err(res_tokens.unwrap_err_unchecked()) };
     };
 
-    dango_parser::parser::parse(tokens)
+    dango_parser::parser::parse(tokens, file)
 }
 
 pub fn compile_string(source: String) -> Result<Program, Vec<CompileError>> {
@@ -31,7 +42,7 @@ pub fn execute_str(runtime: &mut Runtime, source: &str) -> Result<Value, DangoEr
         let result = runtime.run(program);
         match result {
             Ok(value) => Ok(value),
-            Err(err) => Err(DangoError::Runtime(err)),
+            Err(err) => Err(DangoError::Runtime(err, runtime.trace().cloned().map(Box::new))),
         }
     } else {
         Err(DangoError::Compile(unsafe { program.unwrap_err_unchecked() }))
@@ -45,9 +56,28 @@ pub fn execute_string(runtime: &mut Runtime, source: String) -> Result<Value, Da
         let result = runtime.run(program);
         match result {
             Ok(value) => Ok(value),
-            Err(err) => Err(DangoError::Runtime(err)),
+            Err(err) => Err(DangoError::Runtime(err, runtime.trace().cloned().map(Box::new))),
         }
     } else {
         Err(DangoError::Compile(unsafe { program.unwrap_err_unchecked() }))
     }
 }
+
+/// Like `execute_str`, but appends the compiled lines to the program already loaded in `runtime` instead of
+/// replacing it. Line `n` of the session stays line `n`, so `(j)` can jump back to earlier lines.
+pub fn execute_str_appended(runtime: &mut Runtime, source: &str) -> Result<Value, DangoError> {
+    execute_str_appended_in(runtime, source, SourceId::default())
+}
+
+/// Like `execute_str_appended`, but errors and traces point at `file`, so each chunk can be kept in a
+/// `SourceMap` and errors on earlier lines are rendered with the chunk they came from.
+pub fn execute_str_appended_in(runtime: &mut Runtime, source: &str, file: SourceId) -> Result<Value, DangoError> {
+    let mut program = compile_unchecked(source, file).map_err(DangoError::Compile)?;
+
+    // `parse` ends every program with a `Nop` line, which would shift the line numbers of the next chunk. Jumping
+    // to the line after the last one still ends the program, like it does for `execute_str`
+    program.pop_line();
+    dango_parser::parser::validate_jumps(&program, runtime.program().lines() + program.lines() + 1).map_err(DangoError::Compile)?;
+
+    runtime.run_appended(program).map_err(|err| DangoError::Runtime(err, runtime.trace().cloned().map(Box::new)))
+}
@@ -0,0 +1,192 @@
+//! Loads programs that are split over several files with `import`.
+//!
+//! Every import is replaced by a jump to a copy of the imported file, which is linked after the importing file
+//! and jumps back to the line after the import when it's done. The importing file keeps its own line numbers,
+//! and the jumps in the imported file are moved along with it, so `(j)` works the same in both.
+
+use std::path::{Path, PathBuf};
+
+use dango_errors::{CompileError, CompileErrorKind, SourceLocation};
+use dango_errors::source_map::SourceMap;
+use dango_parser::tokenizer::{Token, TokenKind};
+use dango_runtime::instructions::{Instruction, Program};
+
+/// Compiles `source`, which was read from `path`, along with every file that it imports. Imports are relative
+/// to the directory of the file that imports them. Every file is added to `sources`, so errors and runtime
+/// traces can be rendered with the file they are in.
+pub fn load_str(sources: &mut SourceMap, path: &Path, source: &str) -> Result<Program, Vec<CompileError>> {
+    let mut loader = Loader {
+        sources,
+        loading: vec![std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())],
+    };
+
+    loader.load(path, source)
+}
+
+struct Loader<'a> {
+    sources: &'a mut SourceMap,
+    // The files that are being loaded right now, to catch files that end up importing themselves
+    loading: Vec<PathBuf>,
+}
+
+impl Loader<'_> {
+    fn load(&mut self, path: &Path, source: &str) -> Result<Program, Vec<CompileError>> {
+        let file = self.sources.add(path.display().to_string(), source);
+
+        let spans = dango_parser::span_tokenizer::tokenize_into_spans(source, file)?;
+        let mut tokens = dango_parser::tokenizer::tokenize(spans, file)?;
+
+        let mut errors = vec![];
+        let imports = find_imports(&tokens, &mut errors);
+
+        let mut modules = vec![];
+        for &(index, line) in &imports {
+            let token = &tokens[index];
+            let TokenKind::Import(import) = &token.kind else {
+                unreachable!();
+            };
+
+            match self.load_module(path, import) {
+                Ok(module) => modules.push((line, module?)),
+                Err(kind) => errors.push(CompileError::new(kind, token.line, token.column)),
+            }
+        }
+
+        // Back to front, so replacing an import doesn't move the ones that are left
+        for &(index, _) in imports.iter().rev() {
+            // Jump to the line of the import for now, which is moved to the module once its line is known
+            let (line, column) = (tokens[index].line, tokens[index].column);
+            tokens.splice(index..=index, [
+                Token::new(TokenKind::Jump, line, column),
+                Token::new(TokenKind::Int(line as i64), line, column),
+                Token::new(TokenKind::Stick, line, column),
+            ]);
+        }
+
+        if !errors.is_empty() {
+            return Err(errors.into_iter().map(|err| err.in_file(file)).collect());
+        }
+
+        let mut program = dango_parser::parser::parse(tokens, file)?;
+        dango_parser::parser::validate_jumps(&program, program.lines())?;
+
+        if modules.is_empty() {
+            return Ok(program);
+        }
+
+        // The file would run into the modules after its last line, so it skips over them to the `Nop` at the end.
+        // Both stand in for the `Nop`, so they are where it was, the line after the last one
+        let end_location = program.line_location(program.lines() - 1);
+        program.pop_line();
+        let end = program.lines() + modules.iter().map(|(_, module)| module.lines()).sum::<usize>() + 2;
+        program.add_line(vec![Instruction::Int(end as i64), Instruction::Jump]);
+        locate_last_line(&mut program, end_location);
+
+        for (line, mut module) in modules {
+            program.get_line_mut(line - 1)[0] = Instruction::Int(program.lines() as i64 + 1);
+
+            make_relocatable(&mut module)?;
+            let module_end = module.line_location(module.lines() - 1);
+            module.pop_line();
+            module.add_line(vec![Instruction::Int(line as i64 + 1), Instruction::Jump]);
+            locate_last_line(&mut module, module_end);
+
+            program.extend(module);
+        }
+
+        program.add_line(vec![Instruction::Nop]);
+        locate_last_line(&mut program, end_location);
+
+        Ok(program)
+    }
+
+    // The outer error is for the import itself, the inner one for errors inside of the imported file
+    fn load_module(&mut self, importer: &Path, import: &str) -> Result<Result<Program, Vec<CompileError>>, CompileErrorKind> {
+        let path = importer.parent().unwrap_or(Path::new("")).join(import);
+        let failed = |err: std::io::Error| CompileErrorKind::ImportFailed(import.to_string(), err.to_string());
+
+        let canonical = std::fs::canonicalize(&path).map_err(failed)?;
+        if self.loading.contains(&canonical) {
+            return Err(CompileErrorKind::CyclicImport(import.to_string()));
+        }
+
+        let source = std::fs::read_to_string(&path).map_err(failed)?;
+
+        self.loading.push(canonical);
+        let module = self.load(&path, &source);
+        self.loading.pop();
+
+        Ok(module)
+    }
+}
+
+fn locate_last_line(program: &mut Program, location: Option<SourceLocation>) {
+    if let Some(location) = location {
+        program.set_line_location(program.lines() - 1, location);
+    }
+}
+
+// Finds the token index and line of every import, which have to be on lines of their own since the module jumps
+// back to the start of the next line.
+fn find_imports(tokens: &[Token], errors: &mut Vec<CompileError>) -> Vec<(usize, usize)> {
+    let mut imports = vec![];
+
+    let mut line = 1;
+    let mut on_line: Vec<usize> = vec![];
+    let mut alone = true;
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::Newline | TokenKind::Eof => {
+                if on_line.len() == 1 && alone {
+                    imports.push((on_line[0], line));
+                } else {
+                    for &index in &on_line {
+                        errors.push(CompileError::new(CompileErrorKind::ImportNotAlone, tokens[index].line, tokens[index].column));
+                    }
+                }
+
+                line += 1;
+                on_line.clear();
+                alone = true;
+            }
+            TokenKind::Import(_) => on_line.push(index),
+            TokenKind::Comment => (),
+            _ => alone = false,
+        }
+    }
+
+    imports
+}
+
+// Constant jumps count lines from the top of their own file. `Program::extend` moves `Line` along with the
+// rest of the module, so turning them into `Line` keeps them pointing at the same lines once it's linked.
+// Computed jumps can't be moved, so they are rejected instead of landing in some other file.
+fn make_relocatable(module: &mut Program) -> Result<(), Vec<CompileError>> {
+    let mut errors = vec![];
+
+    for line in 0..module.lines() {
+        for index in 0..module.get_line(line).len() {
+            let instructions = module.get_line_mut(line);
+            if !matches!(instructions[index], Instruction::Jump) {
+                continue;
+            }
+
+            match index.checked_sub(1).map(|previous| instructions[previous]) {
+                Some(Instruction::Int(target)) => instructions[index - 1] = Instruction::Line(target as usize),
+                Some(Instruction::Line(_)) => (),
+                _ => {
+                    let location = module.location(line, index).unwrap_or(SourceLocation::new(0, 0));
+                    let kind = CompileErrorKind::ComputedJumpInImport;
+                    errors.push(CompileError::new(kind, location.line, location.column).in_file(location.file));
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
@@ -0,0 +1,278 @@
+//! A tiny line editor for the REPL. `read_line` alone turns the arrow keys into `^[[A` and forgets everything
+//! once the REPL closes, so on Unix terminals this switches the terminal into raw mode with `stty` and does the
+//! editing itself. Pipes and other platforms fall back to plain `read_line`, so `dango < file` keeps working.
+
+use std::fs::OpenOptions;
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use dango_core::dango_errors::render::char_width;
+
+const MAX_HISTORY: usize = 1000;
+
+pub struct LineEditor {
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+    interactive: bool,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        let history_path = history_path();
+
+        let mut history = history_path.as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|contents| contents.lines().map(str::to_string).collect::<Vec<String>>())
+            .unwrap_or_default();
+
+        if history.len() > MAX_HISTORY {
+            history.drain(..history.len() - MAX_HISTORY);
+
+            if let Some(path) = &history_path {
+                let _ = std::fs::write(path, history.join("\n") + "\n");
+            }
+        }
+
+        Self {
+            history,
+            history_path,
+            interactive: cfg!(unix) && std::io::stdin().is_terminal() && std::io::stdout().is_terminal(),
+        }
+    }
+
+    /// Prints `prompt` and reads a line without its line ending. Returns `None` once the input runs out or
+    /// the user presses Ctrl+D on an empty line.
+    pub fn read_line(&mut self, prompt: &str) -> std::io::Result<Option<String>> {
+        print!("{}", prompt);
+        std::io::stdout().flush()?;
+
+        if self.interactive && let Some(_raw_mode) = RawMode::enable() {
+            return self.edit_line(prompt);
+        }
+
+        let mut line = String::new();
+        if std::io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+
+    /// Remembers `line` for the arrow keys and appends it to the history file.
+    pub fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().is_some_and(|last| last == line) {
+            return;
+        }
+
+        self.history.push(line.to_string());
+
+        if let Some(path) = &self.history_path {
+            // Losing the history isn't worth interrupting the user over
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+
+    fn edit_line(&mut self, prompt: &str) -> std::io::Result<Option<String>> {
+        let mut stdin = std::io::stdin().lock();
+
+        let mut buffer: Vec<char> = vec![];
+        let mut cursor = 0;
+
+        // `history.len()` is the line being typed, anything below that is a recalled entry
+        let mut recalled = self.history.len();
+        let mut draft = vec![];
+
+        loop {
+            let Some(byte) = read_byte(&mut stdin)? else {
+                println!();
+                return Ok(None);
+            };
+
+            match byte {
+                b'\r' | b'\n' => {
+                    println!();
+                    return Ok(Some(buffer.iter().collect()));
+                }
+                // Ctrl+C throws the line away instead of killing the REPL (and leaving the terminal in raw mode)
+                0x03 => {
+                    println!("^C");
+                    return Ok(Some(String::new()));
+                }
+                0x04 if buffer.is_empty() => {
+                    println!();
+                    return Ok(None);
+                }
+                0x01 => cursor = 0,
+                0x05 => cursor = buffer.len(),
+                // Without a guard, so backspace at the start of the line doesn't fall through and insert itself
+                0x08 | 0x7f => {
+                    let Some(previous) = cursor.checked_sub(1) else { continue };
+
+                    cursor = previous;
+                    buffer.remove(cursor);
+                }
+                0x1b => match read_escape(&mut stdin)? {
+                    Key::Up => if recalled > 0 {
+                        if recalled == self.history.len() {
+                            draft = buffer.clone();
+                        }
+
+                        recalled -= 1;
+                        buffer = self.history[recalled].chars().collect();
+                        cursor = buffer.len();
+                    }
+                    Key::Down => if recalled < self.history.len() {
+                        recalled += 1;
+                        buffer = match self.history.get(recalled) {
+                            Some(line) => line.chars().collect(),
+                            None => std::mem::take(&mut draft),
+                        };
+                        cursor = buffer.len();
+                    }
+                    Key::Left => cursor = cursor.saturating_sub(1),
+                    Key::Right => cursor = (cursor + 1).min(buffer.len()),
+                    Key::Home => cursor = 0,
+                    Key::End => cursor = buffer.len(),
+                    Key::Delete => if cursor < buffer.len() {
+                        buffer.remove(cursor);
+                    }
+                    Key::Unknown => (),
+                }
+                0x20.. => if let Some(c) = read_char(&mut stdin, byte)? {
+                    buffer.insert(cursor, c);
+                    cursor += 1;
+                }
+                _ => (),
+            }
+
+            redraw(prompt, &buffer, cursor)?;
+        }
+    }
+}
+
+enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+    Unknown,
+}
+
+fn read_byte(stdin: &mut impl Read) -> std::io::Result<Option<u8>> {
+    let mut byte = [0u8];
+
+    match stdin.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+// Decodes the rest of a UTF-8 sequence whose first byte has already been read
+fn read_char(stdin: &mut impl Read, first: u8) -> std::io::Result<Option<char>> {
+    let len = match first {
+        0x00..0x80 => 1,
+        0xc0..0xe0 => 2,
+        0xe0..0xf0 => 3,
+        0xf0..0xf8 => 4,
+        _ => return Ok(None),
+    };
+
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        let Some(byte) = read_byte(stdin)? else { return Ok(None) };
+        bytes.push(byte);
+    }
+
+    Ok(std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()))
+}
+
+// Reads what's left of an escape sequence after the ESC, like `[A` for the up arrow or `[3~` for delete
+fn read_escape(stdin: &mut impl Read) -> std::io::Result<Key> {
+    let Some(b'[' | b'O') = read_byte(stdin)? else {
+        return Ok(Key::Unknown);
+    };
+
+    let mut parameter = String::new();
+    while let Some(byte) = read_byte(stdin)? {
+        if !(0x40..=0x7e).contains(&byte) {
+            parameter.push(byte as char);
+            continue;
+        }
+
+        return Ok(match (byte, parameter.as_str()) {
+            (b'A', _) => Key::Up,
+            (b'B', _) => Key::Down,
+            (b'C', _) => Key::Right,
+            (b'D', _) => Key::Left,
+            (b'H', _) | (b'~', "1" | "7") => Key::Home,
+            (b'F', _) | (b'~', "4" | "8") => Key::End,
+            (b'~', "3") => Key::Delete,
+            _ => Key::Unknown,
+        });
+    }
+
+    Ok(Key::Unknown)
+}
+
+fn redraw(prompt: &str, buffer: &[char], cursor: usize) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+
+    write!(stdout, "\r{}{}\x1b[K", prompt, buffer.iter().collect::<String>())?;
+
+    // The terminal moves by columns, and CJK characters take up two of them
+    let behind = display_width(&buffer[cursor..]);
+    if behind > 0 {
+        write!(stdout, "\x1b[{}D", behind)?;
+    }
+
+    stdout.flush()
+}
+
+/// How many terminal columns `chars` take up, see `char_width`.
+pub fn display_width(chars: &[char]) -> usize {
+    chars.iter().map(|&c| char_width(c)).sum()
+}
+
+fn history_path() -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+
+    Some(PathBuf::from(home).join(".dango_history"))
+}
+
+// Puts the terminal back the way it was when dropped, even if reading the line failed
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> Option<Self> {
+        let output = Command::new("stty").arg("-g").stdin(Stdio::inherit()).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+
+        let saved = String::from_utf8(output.stdout).ok()?.trim().to_string();
+
+        stty(&["-icanon", "-echo", "-isig", "min", "1", "time", "0"]).then_some(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[self.saved.as_str()]);
+    }
+}
+
+fn stty(args: &[&str]) -> bool {
+    Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .status()
+        .is_ok_and(|status| status.success())
+}
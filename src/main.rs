@@ -1,76 +1,127 @@
 // This project turned from a dream to an annoyance
 
-use std::io::Write;
+use std::io::IsTerminal;
+use std::path::Path;
 
 use dango_core::*;
+use dango_errors::DangoError;
+use dango_errors::render::Renderer;
+use dango_errors::source_map::SourceMap;
 use dango_runtime::runtime::Runtime;
 
+mod editor;
+mod repl;
+
 #[cfg(test)]
 mod tests;
 
 static DANGO_VERSION: &str = "0.11.0";
 
-fn repl() -> std::io::Result<()> {
-    let repl_string = format!("
-   _|_
-  \x1b[0;91m/@@@\\\x1b[0m  | Dango {}
-  \x1b[0;91m\\@@@/\x1b[0m  |
-  \x1b[0;93m/%%%\\\x1b[0m  | Documentation: https://raiseafloppafan3925.github.io/dango
-  \x1b[0;93m\\%%%/\x1b[0m  | 'exit' to exit
-  \x1b[0;92m/***\\\x1b[0m  |
-  \x1b[0;92m\\***/\x1b[0m  | If you find any bugs, please report them at https://github.com/raiseAfloppaFan3925/dango/issues
-    |    | We're back better than ever!
-    |
-", DANGO_VERSION);
-
-    println!("{}", repl_string);
-
-    loop {
-        let mut source: String = String::new();
-
-        print!("--\x1b[0;92m(O)\x1b[0;93m(O)\x1b[0;91m(O)\x1b[0m > ");
-        let _ = std::io::stdout().flush();
+static USAGE: &str = "usage: dango [--input <file>] [--error-format=human|json] [--] [<path> [args...]]
+       dango explain <code>";
 
-        std::io::stdin().read_line(&mut source)?; // This is just the CLI for Dango so why make it (the CLI) multi-threaded?
-
-        if source.trim() == "exit" {
-            break;
-        }
-
-        let mut runtime = Runtime::new();
+#[derive(Default, PartialEq)]
+enum ErrorFormat {
+    #[default]
+    Human,
+    // One JSON object per line on stderr, for editors and CI
+    Json,
+}
 
-        dango_runtime::stdlib::load_io(&mut runtime);
-        dango_runtime::stdlib::load_chrono(&mut runtime);
-        dango_runtime::stdlib::load_math(&mut runtime);
+#[derive(Default)]
+struct Options {
+    path: Option<String>,
+    input: Option<String>,
+    error_format: ErrorFormat,
+    // The error code to explain instead of running anything
+    explain: Option<String>,
+    // What `:env-args` returns, which is the name of the binary, the path and the arguments after it
+    env_args: Vec<String>,
+}
 
-        let value = dango_utils::execute_str(&mut runtime, source.as_str());
+fn parse_error_format(format: &str) -> Result<ErrorFormat, String> {
+    match format {
+        "human" => Ok(ErrorFormat::Human),
+        "json" => Ok(ErrorFormat::Json),
+        _ => Err(format!("unknown error format '{}', expected 'human' or 'json'", format)),
+    }
+}
 
-        // Prevents any `write(stdout)` or `eat` commands from appearing AFTER what's about to be printed below
-        // Doesn't matter, this is a REPL
-        std::io::stdout().flush()?;
-        
-        match value {
-            Ok(value) => println!("\n{}", value),
-            Err(err) => eprintln!("\n{}", err), // Don't exit, this is a REPL
+// Options have to come before the path, everything after it is left for `:env-args`. A file called `explain`
+// is run instead of being taken for the subcommand, and `--` ends the options for paths that look like one.
+fn parse_args(all_args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = all_args.iter().skip(1);
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => match args.next() {
+                Some(input) => options.input = Some(input.clone()),
+                None => return Err("`--input` expects a path".to_string()),
+            }
+            _ if arg.starts_with("--input=") => options.input = Some(arg["--input=".len()..].to_string()),
+            "--error-format" => match args.next() {
+                Some(format) => options.error_format = parse_error_format(format)?,
+                None => return Err("`--error-format` expects 'human' or 'json'".to_string()),
+            }
+            _ if arg.starts_with("--error-format=") => {
+                options.error_format = parse_error_format(&arg["--error-format=".len()..])?;
+            }
+            "--" => {
+                path = args.next();
+                break;
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option '{}'", arg)),
+            "explain" if !Path::new(arg).exists() => match args.next() {
+                Some(code) => {
+                    options.explain = Some(code.clone());
+                    break;
+                }
+                None => return Err("`explain` expects an error code, like D0001".to_string()),
+            }
+            _ => {
+                path = Some(arg);
+                break;
+            }
         }
+    }
 
-        source.clear();
+    if let Some(path) = path {
+        options.path = Some(path.clone());
+        options.env_args = all_args.iter().take(1).chain([path]).chain(args).cloned().collect();
     }
 
-    std::process::exit(0);
+    Ok(options)
 }
 
 fn main() -> std::io::Result<()> {
     let args = std::env::args().collect::<Vec<String>>();
 
-    if args.len() < 2 {
-        repl()?;
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("Error: {message}\n{USAGE}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(code) = options.explain {
+        let Some(explanation) = dango_errors::explain::explain(&code) else {
+            eprintln!("Error: '{code}' is not an error code");
+            std::process::exit(1);
+        };
+
+        println!("{}\n\n{explanation}", code.to_ascii_uppercase());
         std::process::exit(0);
     }
 
-    let path = args.get(1).unwrap();
+    let Some(path) = options.path else {
+        repl::repl()?;
+        std::process::exit(0);
+    };
 
-    let Ok(source) = std::fs::read(path) else {
+    let Ok(source) = std::fs::read(&path) else {
         eprintln!("Error: Could not read from path {path}");
         std::process::exit(1);
     };
@@ -85,13 +136,43 @@ fn main() -> std::io::Result<()> {
     dango_runtime::stdlib::load_io(&mut runtime);
     dango_runtime::stdlib::load_math(&mut runtime);
     dango_runtime::stdlib::load_chrono(&mut runtime);
+    runtime.insert_data(dango_runtime::stdlib::EnvArgs(options.env_args));
 
-    let value = dango_utils::execute_str(&mut runtime, source.as_str());
+    if let Some(input) = options.input {
+        let Ok(file) = std::fs::File::open(&input) else {
+            eprintln!("Error: Could not read from path {input}");
+            std::process::exit(1);
+        };
 
-    if let Err(err) = value {
-        println!("\n{}", err);
-        std::process::exit(1);
+        runtime.set_stdin(Box::new(std::io::BufReader::new(file)));
+    }
+
+    let mut sources = SourceMap::new();
+
+    let program = match dango_utils::loader::load_str(&mut sources, Path::new(&path), &source) {
+        Ok(program) => program,
+        Err(errors) => report(&options.error_format, &sources, &DangoError::Compile(errors)),
+    };
+
+    if let Err(err) = runtime.run(program) {
+        let err = DangoError::Runtime(err, runtime.trace().cloned().map(Box::new));
+        report(&options.error_format, &sources, &err);
     }
 
     Ok(())
 }
+
+fn report(format: &ErrorFormat, sources: &SourceMap, err: &DangoError) -> ! {
+    if *format == ErrorFormat::Json {
+        for diagnostic in err.diagnostics_in(sources) {
+            eprintln!("{}", diagnostic.to_json());
+        }
+        std::process::exit(1);
+    }
+
+    // https://no-color.org
+    let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+    eprint!("\n{}", Renderer::with_sources(sources, color).render(err));
+    std::process::exit(1);
+}
@@ -0,0 +1,185 @@
+//! The interactive side of the CLI. A session keeps one runtime alive for as long as the user wants it, so
+//! values pushed on one line are still there on the next.
+
+use std::io::{IsTerminal, Write};
+
+use dango_core::*;
+use dango_errors::render::Renderer;
+use dango_errors::source_map::SourceMap;
+use dango_runtime::runtime::Runtime;
+
+use super::DANGO_VERSION;
+use super::editor::LineEditor;
+
+static PROMPT: &str = "--\x1b[0;92m(O)\x1b[0;93m(O)\x1b[0;91m(O)\x1b[0m > ";
+
+pub struct Session {
+    runtime: Runtime,
+    // Every chunk entered so far, since a jump can end up erroring on a line from an earlier one
+    sources: SourceMap,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            runtime: new_runtime(),
+            sources: SourceMap::new(),
+        }
+    }
+
+    /// Throws away the stack and every line entered so far.
+    pub fn reset(&mut self) {
+        self.runtime = new_runtime();
+        self.sources = SourceMap::new();
+    }
+
+    /// Handles a `:command` line. These never reach the compiler since no Dango line can start with a `:`.
+    pub fn command(&mut self, line: &str) {
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let name = parts.next().unwrap_or("");
+        let argument = parts.next().unwrap_or("").trim();
+
+        match name {
+            ":help" => println!("{}", HELP),
+            ":stack" => self.runtime.dump_stack(),
+            ":peek" => {
+                let depth = if argument.is_empty() { Ok(0) } else { argument.parse::<usize>() };
+
+                match depth {
+                    Ok(depth) => match self.runtime.peek(depth) {
+                        Ok(value) => println!("{}", value),
+                        Err(err) => eprintln!("{}", err),
+                    }
+                    Err(_) => eprintln!("error: `:peek` expects a depth, found '{}'", argument),
+                }
+            }
+            ":pop" => match self.runtime.pop() {
+                Ok(value) => println!("{}", value),
+                Err(err) => eprintln!("{}", err),
+            }
+            ":clear" => self.runtime.clear_stack(),
+            ":natives" => for name in self.runtime.function_names() {
+                println!("(:{})", name);
+            }
+            ":load" => {
+                if argument.is_empty() {
+                    eprintln!("error: `:load` expects a path");
+                    return;
+                }
+
+                match std::fs::read_to_string(argument) {
+                    Ok(source) => self.eval_named(argument, source.as_str()),
+                    Err(err) => eprintln!("error: could not read from path {}: {}", argument, err),
+                }
+            }
+            ":reset" => self.reset(),
+            _ => eprintln!("error: unknown command '{}', try ':help'", name),
+        }
+    }
+
+    /// The line number that the next entered line will have.
+    pub fn next_line(&self) -> usize {
+        self.runtime.program().lines() + 1
+    }
+
+    pub fn eval(&mut self, source: &str) {
+        self.eval_named("<repl>", source);
+    }
+
+    fn eval_named(&mut self, name: &str, source: &str) {
+        let file = self.sources.add(name, source);
+        let value = dango_utils::execute_str_appended_in(&mut self.runtime, source, file);
+
+        // Prevents any `write(stdout)` or `eat` commands from appearing AFTER what's about to be printed below
+        // Doesn't matter, this is a REPL
+        let _ = std::io::stdout().flush();
+
+        match value {
+            Ok(value) => println!("\n{}", value),
+            // Don't exit, this is a REPL
+            Err(err) => {
+                // https://no-color.org
+                let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+                eprint!("\n{}", Renderer::with_sources(&self.sources, color).render(&err));
+            }
+        }
+    }
+}
+
+static HELP: &str = "\
+:help          show this
+:block         type several lines at once, an empty line ends the block
+:stack         print every value on the stack, bottom first
+:peek [depth]  print the value `depth` values below the top without popping it
+:pop           pop and print the top of the stack
+:clear         empty the stack but keep the lines entered so far
+:natives       list the native functions that can be called
+:load <path>   run a file as if its lines were typed in
+:reset         throw away the stack and every line entered so far
+exit           leave the REPL";
+
+fn new_runtime() -> Runtime {
+    let mut runtime = Runtime::new();
+
+    dango_runtime::stdlib::load_io(&mut runtime);
+    dango_runtime::stdlib::load_chrono(&mut runtime);
+    dango_runtime::stdlib::load_math(&mut runtime);
+
+    runtime
+}
+
+pub fn repl() -> std::io::Result<()> {
+    let repl_string = format!("
+   _|_
+  \x1b[0;91m/@@@\\\x1b[0m  | Dango {}
+  \x1b[0;91m\\@@@/\x1b[0m  |
+  \x1b[0;93m/%%%\\\x1b[0m  | Documentation: https://raiseafloppafan3925.github.io/dango
+  \x1b[0;93m\\%%%/\x1b[0m  | 'exit' to exit, ':help' for commands
+  \x1b[0;92m/***\\\x1b[0m  |
+  \x1b[0;92m\\***/\x1b[0m  | If you find any bugs, please report them at https://github.com/raiseAfloppaFan3925/dango/issues
+    |    | We're back better than ever!
+    |
+", DANGO_VERSION);
+
+    println!("{}", repl_string);
+
+    let mut editor = LineEditor::new();
+    let mut session = Session::new();
+
+    while let Some(line) = editor.read_line(PROMPT)? {
+        editor.add_history(&line);
+
+        match line.trim() {
+            "exit" => break,
+            "" => (),
+            ":block" => {
+                let block = read_block(&mut editor, &session)?;
+                if !block.trim().is_empty() {
+                    session.eval(block.as_str());
+                }
+            }
+            command if command.starts_with(':') => session.command(command),
+            _ => session.eval(line.as_str()),
+        }
+    }
+
+    Ok(())
+}
+
+// Keeps reading lines until an empty one so that multi-line programs can be typed in one go. The prompt shows
+// the line number each line will get, since that's what `(j)` needs.
+fn read_block(editor: &mut LineEditor, session: &Session) -> std::io::Result<String> {
+    let mut lines = vec![];
+
+    while let Some(line) = editor.read_line(format!("{:>4} | ", session.next_line() + lines.len()).as_str())? {
+        if line.trim().is_empty() {
+            break;
+        }
+
+        editor.add_history(&line);
+        lines.push(line);
+    }
+
+    Ok(lines.join("\n"))
+}
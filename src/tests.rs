@@ -4,6 +4,14 @@ use super::*;
 use dango_errors::*;
 use dango_runtime::{Value, runtime::Runtime};
 
+// Most tests only care which error happened, not where
+fn without_trace(result: Result<Value, DangoError>) -> Result<Value, DangoError> {
+    result.map_err(|err| match err {
+        DangoError::Runtime(err, _) => DangoError::Runtime(err, None),
+        err => err,
+    })
+}
+
 #[test]
 fn test_addition() {
     let mut runtime = Runtime::new();
@@ -66,31 +74,714 @@ fn test_compile_errors() {
         )
     ]));
 
+    // `eat` still needs a stick on its own line, so the comment can't carry on to the next one
     let result = dango_utils::compile_str("eat ;: Hello\n:; ----(2)----");
     assert_eq!(result, Err(vec![
         CompileError::new(
-            CompileErrorKind::InvalidToken(";:".to_string()),
+            CompileErrorKind::MultilineComment,
             1,
             5
         ),
-        CompileError::new(
-            CompileErrorKind::InvalidToken("Hello".to_string()),
-            1,
-            8
-        ),
-        CompileError::new(
-            CompileErrorKind::InvalidToken(":;".to_string()),
-            2,
-            1
-        ),
-        // CompileError::new(
-        //     CompileErrorKind::OrphanedStick,
-        //     2,
-        //     4
-        // ),
-        //
-        // This is not here because invalid tokens are span tokenizer errors, which stops the
-        // tokens from ever reaching the tokenizer and the parser which validates the tokens,
-        // which includes making sure that all sticks are bound.
     ]));
 }
+
+#[test]
+fn test_appended_execution() {
+    let mut runtime = Runtime::new();
+
+    let result = dango_utils::execute_str_appended(&mut runtime, "(1)----");
+    assert_eq!(result, Ok(Value::Int(1)));
+
+    // the stack survives between chunks
+    let result = dango_utils::execute_str_appended(&mut runtime, "(+)(2)----");
+    assert_eq!(result, Ok(Value::Int(3)));
+
+    // each chunk is one line, so line 3 can jump back to line 2
+    let result = dango_utils::execute_str_appended(&mut runtime, "(j)(2)(while)(<)(10)---- fetch 0");
+    assert_eq!(result, Ok(Value::Int(11)));
+}
+
+#[test]
+fn test_peek_empty_stack() {
+    let mut runtime = Runtime::new();
+
+    assert!(runtime.peek(0).is_err());
+
+    let result = dango_utils::execute_str(&mut runtime, "(len)----");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::StackUnderflow, None)));
+}
+
+#[test]
+fn test_appended_text() {
+    let mut runtime = Runtime::new();
+
+    // each chunk interns its text separately, so appending has to re-intern it
+    let result = dango_utils::execute_str_appended(&mut runtime, "(')(a)----");
+    assert_eq!(result, Ok(Value::String("a".to_string())));
+
+    let result = dango_utils::execute_str_appended(&mut runtime, "(+)(')(b)---- remove (a)----");
+    assert_eq!(result, Ok(Value::String("ab".to_string())));
+
+    // running text shares the interned string instead of copying it
+    let result = dango_utils::execute_str_appended(&mut runtime, "(b)----");
+    let Ok(Value::RawText(text)) = result else { panic!("expected raw text, got {:?}", result) };
+    let id = (0..).find(|&id| runtime.program().string(id) == "b").unwrap();
+    assert!(std::sync::Arc::ptr_eq(&text, &runtime.program().shared_string(id)));
+}
+
+#[test]
+fn test_link_errors() {
+    let mut runtime = Runtime::new();
+
+    // nothing runs if a function is missing, so `(1)` is never pushed
+    let result = dango_utils::execute_str(&mut runtime, "(1)----\n(:missing)----");
+    assert_eq!(result, Err(DangoError::Runtime(RuntimeError::NonexistentFunction("missing".to_string()), None)));
+    assert_eq!(runtime.stack_len(), 0);
+
+    let result = dango_utils::execute_str(&mut runtime, "(:b)(:a)(:b)----");
+    assert_eq!(result, Err(DangoError::Runtime(RuntimeError::NonexistentFunctions(vec![
+        "a".to_string(),
+        "b".to_string(),
+    ]), None)));
+
+    dango_runtime::stdlib::load_math(&mut runtime);
+    let result = dango_utils::execute_str(&mut runtime, "(:math-abs)(-2)----");
+    assert_eq!(result, Ok(Value::Int(2)));
+}
+
+#[test]
+fn test_fuel() {
+    let mut runtime = Runtime::new();
+    runtime.set_fuel(Some(100));
+
+    let result = dango_utils::execute_str(&mut runtime, "(j)(1)----");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::OutOfFuel, None)));
+    assert!(runtime.is_paused());
+
+    // time slicing a loop gives the same result as running it in one go
+    let source = "(0)----\n(j)(2)(while)(<)(1000)---- fetch 0 (+)(1)----";
+    let mut result = dango_utils::execute_str(&mut runtime, source);
+    while matches!(result, Err(DangoError::Runtime(RuntimeError::OutOfFuel, _))) {
+        runtime.add_fuel(100);
+        result = runtime.resume().map_err(|err| DangoError::Runtime(err, None));
+    }
+    assert_eq!(result, Ok(Value::Int(1000)));
+
+    assert!(runtime.resume().is_err());
+}
+
+#[test]
+fn test_stack_and_memory_limits() {
+    let mut runtime = Runtime::new();
+    runtime.set_max_stack(Some(64));
+
+    let result = dango_utils::execute_str(&mut runtime, "(j)(1)(1)----");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::StackOverflow, None)));
+
+    // `tests/fork-bomb.dango` keeps the stack small, but every dango is twice as big as the last
+    let mut runtime = Runtime::new();
+    runtime.set_memory_limit(Some(1 << 20));
+
+    let result = dango_utils::execute_str(&mut runtime, "(')(fork)----\n(j)(2)---- skewer 2 fetch 0");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::MemoryLimit, None)));
+
+    let mut runtime = Runtime::new();
+
+    let result = dango_utils::execute_str(&mut runtime, "(*)(2)(')(ab)----");
+    assert_eq!(result, Ok(Value::String("ababab".to_string())));
+
+    runtime.set_memory_limit(Some(1024));
+
+    let result = dango_utils::execute_str(&mut runtime, "(*)(1000)(')(ab)----");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::MemoryLimit, None)));
+}
+
+#[test]
+fn test_output_redirection() {
+    use dango_runtime::streams::SharedBuffer;
+
+    let mut runtime = Runtime::new();
+    dango_runtime::stdlib::load_io(&mut runtime);
+
+    let stdout = SharedBuffer::new();
+    let stderr = SharedBuffer::new();
+    runtime.set_stdout(Box::new(stdout.clone()));
+    runtime.set_stderr(Box::new(stderr.clone()));
+
+    let result = dango_utils::execute_str(&mut runtime, "eat (')(Hello, world!)----");
+    assert_eq!(result, Ok(Value::Nil));
+    assert_eq!(stdout.take(), "Hello, world!");
+
+    let result = dango_utils::execute_str(&mut runtime, "(:io-write)(')(stderr)(')(oops)----");
+    assert_eq!(result, Ok(Value::Nil));
+    assert_eq!(stdout.contents(), "");
+    assert_eq!(stderr.contents(), "oops");
+}
+
+#[test]
+fn test_input_redirection() {
+    let mut runtime = Runtime::new();
+    dango_runtime::stdlib::load_io(&mut runtime);
+
+    runtime.set_stdin(Box::new(std::io::Cursor::new("42\r\nsecond line\n")));
+
+    let result = dango_utils::execute_str(&mut runtime, "(:io-input)(0)----");
+    assert_eq!(result, Ok(Value::String("42".to_string())));
+
+    let result = dango_utils::execute_str(&mut runtime, "(:io-input)(0)----");
+    assert_eq!(result, Ok(Value::String("second line".to_string())));
+}
+
+#[test]
+fn test_native_closures() {
+    use std::sync::mpsc;
+
+    let mut runtime = Runtime::new();
+
+    let mut count = 0;
+    runtime.register_closure("count".to_string(), move |_| {
+        count += 1;
+        Ok(Value::Int(count))
+    });
+
+    let (sender, receiver) = mpsc::channel();
+    runtime.register_closure("send".to_string(), move |runtime| {
+        let _ = sender.send(runtime.pop()?);
+        Ok(Value::Nil)
+    });
+
+    let result = dango_utils::execute_str(&mut runtime, "(+)(:count)(:count)----");
+    assert_eq!(result, Ok(Value::Int(3)));
+
+    let result = dango_utils::execute_str(&mut runtime, "(:send)(:count)----");
+    assert_eq!(result, Ok(Value::Nil));
+    assert_eq!(receiver.try_recv(), Ok(Value::Int(3)));
+
+    // the runtime can still be moved to another thread
+    let result = std::thread::spawn(move || dango_utils::execute_str(&mut runtime, "(:count)----")).join();
+    assert_eq!(result.ok(), Some(Ok(Value::Int(4))));
+}
+
+#[test]
+fn test_runtime_data() {
+    struct Counter(i64);
+
+    fn count(runtime: &mut Runtime) -> Result<Value, RuntimeError> {
+        let Some(counter) = runtime.data_mut::<Counter>() else {
+            return Err(RuntimeError::CustomError("no counter".to_string()));
+        };
+
+        counter.0 += 1;
+        Ok(Value::Int(counter.0))
+    }
+
+    let mut runtime = Runtime::new();
+    runtime.register_function("count".to_string(), count);
+
+    let result = dango_utils::execute_str(&mut runtime, "(:count)----");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::CustomError("no counter".to_string()), None)));
+
+    assert!(runtime.insert_data(Counter(10)).is_none());
+    assert_eq!(runtime.insert_data(String::from("unrelated")), None);
+
+    let result = dango_utils::execute_str(&mut runtime, "(+)(:count)(:count)----");
+    assert_eq!(result, Ok(Value::Int(23)));
+    assert_eq!(runtime.data::<Counter>().map(|counter| counter.0), Some(12));
+    assert_eq!(runtime.remove_data::<String>().as_deref(), Some("unrelated"));
+}
+
+#[test]
+fn test_env_args() {
+    let args = ["dango", "--input", "in.txt", "--error-format=json", "prog.dango", "x", "--input"]
+        .map(String::from);
+
+    // options before the path are the CLI's, everything after it is the program's
+    let options = parse_args(&args).unwrap();
+    assert_eq!(options.path.as_deref(), Some("prog.dango"));
+    assert_eq!(options.input.as_deref(), Some("in.txt"));
+    assert_eq!(options.env_args, ["dango", "prog.dango", "x", "--input"]);
+
+    let mut runtime = Runtime::new();
+    dango_runtime::stdlib::load_io(&mut runtime);
+    runtime.insert_data(dango_runtime::stdlib::EnvArgs(options.env_args));
+
+    let result = dango_utils::execute_str(&mut runtime, "(:env-args)----");
+    assert_eq!(result, Ok(Value::dango_from_vec(["dango", "prog.dango", "x", "--input"].map(|arg| Value::String(arg.to_string())).to_vec())));
+}
+
+#[test]
+fn test_explain_args() {
+    let options = parse_args(&["dango", "explain", "D0001"].map(String::from)).unwrap();
+    assert_eq!((options.explain.as_deref(), options.path), (Some("D0001"), None));
+
+    // `--` runs a file called `explain`, even if there is no such file yet to tell them apart
+    let options = parse_args(&["dango", "--", "explain", "D0001"].map(String::from)).unwrap();
+    assert_eq!((options.explain, options.path.as_deref()), (None, Some("explain")));
+    assert_eq!(options.env_args, ["dango", "explain", "D0001"]);
+}
+
+#[test]
+fn test_native_arguments() {
+    let mut runtime = Runtime::new();
+    dango_runtime::stdlib::load_math(&mut runtime);
+    runtime.register_function("join".to_string(), dango_runtime::native_fn!(|a: String, b: String| a + b.as_str()));
+
+    let result = dango_utils::execute_str(&mut runtime, "(:math-pow)(3)(2)----");
+    assert_eq!(result, Ok(Value::Int(8)));
+
+    let result = dango_utils::execute_str(&mut runtime, "(:math-logb)(2)(8)----");
+    assert_eq!(result, Ok(Value::Float(3.0)));
+
+    let result = dango_utils::execute_str(&mut runtime, "(:join)(')(b)(')(a)----");
+    assert_eq!(result, Ok(Value::String("ab".to_string())));
+
+    let result = dango_utils::execute_str(&mut runtime, "(:math-abs)(')(one)----");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::InvalidArgument {
+        function: "math-abs".to_string(),
+        position: 1,
+        expected: "a number",
+        found: "a string",
+    }, None)));
+
+    let result = dango_utils::execute_str(&mut runtime, "(:join)(')(b)(1)----");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::InvalidArgument {
+        function: "join".to_string(),
+        position: 2,
+        expected: "a string",
+        found: "an integer",
+    }, None)));
+
+    let result = dango_utils::execute_str(&mut runtime, "(:math-logb)(2)()----");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::InvalidArgument {
+        function: "math-logb".to_string(),
+        position: 2,
+        expected: "a number",
+        found: "nil",
+    }, None)));
+}
+
+#[test]
+fn test_runtime_trace() {
+    let mut runtime = Runtime::new();
+
+    // the loop runs twice before `(+)` runs out of values on line 3
+    let source = "(0)----\n(j)(3)(while)(<)(2)---- fetch 0 (+)(1)----\n(1)----  (-)----";
+    let result = dango_utils::execute_str(&mut runtime, source);
+    let Err(DangoError::Runtime(RuntimeError::StackUnderflow, Some(trace))) = result else {
+        panic!("expected a stack underflow with a trace, got {:?}", result);
+    };
+
+    assert_eq!(trace.line, 3);
+    assert_eq!(trace.dumpling, 2);
+    assert_eq!(trace.location, Some(SourceLocation::new(3, 10)));
+    assert_eq!(trace.jumps, vec![(SourceLocation::new(2, 1), SourceLocation::new(3, 1))]);
+    assert_eq!(runtime.trace(), Some(&*trace));
+
+    dango_runtime::stdlib::load_math(&mut runtime);
+    let result = dango_utils::execute_str(&mut runtime, "(:math-sqrt)----");
+    let Err(DangoError::Runtime(_, Some(trace))) = result else {
+        panic!("expected a trace, got {:?}", result);
+    };
+    assert_eq!(trace.function.as_deref(), Some("math-sqrt"));
+    assert_eq!(trace.location, Some(SourceLocation::new(1, 1)));
+}
+
+#[test]
+fn test_rendering() {
+    let mut runtime = Runtime::new();
+
+    let source = "(1)----\n(+)(2)(x----";
+    let err = dango_utils::execute_str(&mut runtime, source).unwrap_err();
+    assert_eq!(render::Renderer::new(source, false).render(&err), "\
+error[D0008]: dumplings must be closed with a ')'
+ --> 2:7
+  |
+2 | (+)(2)(x----
+  |       ^^^^^^
+");
+
+    let source = "(1)----\n(+)(2)----  (-)----";
+    let err = dango_utils::execute_str(&mut runtime, source).unwrap_err();
+    assert_eq!(render::Renderer::new(source, false).render(&err), "\
+error[D0111]: the stack cannot be popped from when it is already empty
+ --> 2:13
+  |
+2 | (+)(2)----  (-)----
+  |             ^^^
+");
+
+    // wide characters take up two columns of the terminal, so the carets have to move over by two for each
+    let source = "remove (団子)---- (+)(')(🍡)(1)----";
+    let err = dango_utils::execute_str(&mut runtime, source).unwrap_err();
+    assert_eq!(render::Renderer::new(source, false).render(&err), "\
+error[D0102]: incorrect addition types
+ --> 1:17
+  |
+1 | remove (団子)---- (+)(')(🍡)(1)----
+  |                   ^^^
+");
+
+    let source = "(')(🍡x----";
+    let err = dango_utils::execute_str(&mut runtime, source).unwrap_err();
+    assert!(render::Renderer::new(source, false).render(&err).ends_with("\n  |    ^^^^^^^^\n"));
+}
+
+#[test]
+fn test_diagnostics() {
+    let mut runtime = Runtime::new();
+
+    let source = "(1)----\n(+)(2)(x----";
+    let err = dango_utils::execute_str(&mut runtime, source).unwrap_err();
+    let diagnostics = err.diagnostics(source);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].to_json(), "{\"code\":\"D0008\",\"kind\":\"UnterminatedDumpling\",\"severity\":\"error\",\
+        \"message\":\"dumplings must be closed with a ')'\",\"file\":null,\"line\":2,\"column\":7,\"utf16_column\":7,\"end_line\":2,\"end_column\":13,\"end_utf16_column\":13}");
+
+    let source = "(:missing)(\"quoted\")----";
+    let err = dango_utils::execute_str(&mut runtime, source).unwrap_err();
+    assert_eq!(err.diagnostics(source)[0].to_json(), "{\"code\":\"D0105\",\"kind\":\"NonexistentFunction\",\"severity\":\"error\",\
+        \"message\":\"function `:missing` does not exist\",\"file\":null,\"line\":null,\"column\":null,\"utf16_column\":null,\"end_line\":null,\"end_column\":null,\"end_utf16_column\":null}");
+
+    let err = DangoError::Runtime(RuntimeError::CustomError("a \"b\"\n".to_string()), None);
+    assert_eq!(err.diagnostics("")[0].message, "a \"b\"\n");
+    assert!(err.diagnostics("")[0].to_json().contains("\"message\":\"a \\\"b\\\"\\n\""));
+}
+
+#[test]
+fn test_error_codes() {
+    let mut runtime = Runtime::new();
+
+    for code in explain::CODES {
+        assert!(explain::explain(code).is_some(), "{} has no explanation", code);
+    }
+    assert!(explain::explain("d0111").is_some());
+    assert!(explain::explain("D9999").is_none());
+
+    let result = dango_utils::execute_str(&mut runtime, "(1)---- fetch");
+    assert_eq!(result, Err(DangoError::Compile(vec![CompileError::new(CompileErrorKind::MissingCount("fetch".to_string()), 1, 9)])));
+
+    let result = dango_utils::execute_str(&mut runtime, "(j)(')(two)----");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::InvalidJump("a string"), None)));
+
+    let result = dango_utils::execute_str(&mut runtime, "skewer 6");
+    assert_eq!(without_trace(result), Err(DangoError::Runtime(RuntimeError::SkewerTooShort(6), None)));
+
+    let err = RuntimeError::NegativeRepeat(-1);
+    assert_eq!(err.code(), "D0117");
+    assert!(explain::CODES.contains(&err.code()));
+}
+
+#[test]
+fn test_error_sources() {
+    use std::error::Error;
+
+    let mut runtime = Runtime::new();
+    runtime.register_function("parse".to_string(), |runtime| {
+        let text = runtime.pop_as::<String>()?;
+        let number = text.parse::<i64>().map_err(|err| RuntimeError::host(format!("could not parse '{}'", text), err))?;
+
+        Ok(Value::Int(number))
+    });
+
+    let result = dango_utils::execute_str(&mut runtime, "(:parse)(')(12)----");
+    assert_eq!(result, Ok(Value::Int(12)));
+
+    // errors work with `?` in functions returning `Box<dyn Error>`
+    let run = |runtime: &mut Runtime, source: &str| -> Result<Value, Box<dyn Error>> {
+        Ok(dango_utils::execute_str(runtime, source)?)
+    };
+    let err = run(&mut runtime, "(:parse)(')(twelve)----").unwrap_err();
+    assert_eq!(err.to_string().lines().next(), Some("error: could not parse 'twelve'"));
+    assert!(err.source().is_some_and(|source| source.is::<std::num::ParseIntError>()));
+
+    let err = dango_utils::execute_str(&mut runtime, "(:parse)(')(twelve)----").unwrap_err();
+    assert_eq!(err.clone(), err);
+    assert!(format!("{:?}", err).starts_with("Runtime(Host(\"could not parse 'twelve'\""));
+    assert_eq!(err.diagnostics("")[0].message, "could not parse 'twelve': invalid digit found in string");
+
+    dango_runtime::stdlib::load_io(&mut runtime);
+    let err = dango_utils::execute_str(&mut runtime, "(:io-write)(')(/nonexistent/dango.txt)(')(hi)----").unwrap_err();
+    let DangoError::Runtime(RuntimeError::Io(message, _), _) = &err else {
+        panic!("expected an io error, got {:?}", err);
+    };
+    assert_eq!(message, "`:io-write` failed to write to `/nonexistent/dango.txt`");
+    assert!(err.source().is_some_and(|source| source.is::<std::io::Error>()));
+}
+
+#[test]
+fn test_comments() {
+    let mut runtime = Runtime::new();
+
+    let result = dango_utils::execute_str(&mut runtime, "(+)(1) ;: (one) :; (2)---- ;: three :;");
+    assert_eq!(result, Ok(Value::Int(3)));
+
+    // comments spanning lines don't shift the line numbers of jumps
+    let result = dango_utils::execute_str(&mut runtime, ";: skip\nthe next line :; (j)(4)----\n(1)----\n(2)----");
+    assert_eq!(result, Ok(Value::Int(2)));
+
+    let result = dango_utils::execute_str(&mut runtime, "(+)(1) ;: one\ntwo :; (2)----");
+    assert_eq!(result, Err(DangoError::Compile(vec![CompileError::new(CompileErrorKind::MultilineComment, 1, 8)])));
+
+    // keywords are instructions too, so a comment after one can't end the line either
+    let result = dango_utils::execute_str(&mut runtime, "eat ;: x\n:; (1)----");
+    assert_eq!(result, Err(DangoError::Compile(vec![CompileError::new(CompileErrorKind::MultilineComment, 1, 5)])));
+    let result = dango_utils::execute_str(&mut runtime, "(1)(2)---- fetch 1 ;: x\n:; (1)----");
+    assert_eq!(result, Err(DangoError::Compile(vec![CompileError::new(CompileErrorKind::MultilineComment, 1, 20)])));
+
+    let result = dango_utils::execute_str(&mut runtime, "(1)----\n;: never closed");
+    assert_eq!(result, Err(DangoError::Compile(vec![CompileError::new(CompileErrorKind::UnterminatedComment, 2, 1)])));
+}
+
+#[test]
+fn test_escaped_dumplings() {
+    let mut runtime = Runtime::new();
+
+    let result = dango_utils::execute_str(&mut runtime, r"(')\(\(hello\)\n\t\\ \u{3b1} :io-input)----");
+    assert_eq!(result, Ok(Value::String("(hello)\n\t\\ \u{3b1} :io-input".to_string())));
+
+    // escaped dumplings are always text, and plain dumplings don't know about escapes
+    let result = dango_utils::execute_str(&mut runtime, r"(')\(+)----");
+    assert_eq!(result, Ok(Value::String("+".to_string())));
+    let result = dango_utils::execute_str(&mut runtime, r"(')(C:\n)----");
+    assert_eq!(result, Ok(Value::String(r"C:\n".to_string())));
+
+    let result = dango_utils::compile_str(r"(1)---- \(a \q)----");
+    assert_eq!(result, Err(vec![CompileError::new(CompileErrorKind::InvalidEscape(r"\q".to_string()), 1, 13)]));
+
+    let result = dango_utils::compile_str(r"\(\u{d800})----");
+    assert_eq!(result, Err(vec![CompileError::new(CompileErrorKind::InvalidUnicodeEscape(r"\u{d800}".to_string()), 1, 3)]));
+
+    let result = dango_utils::compile_str(r"\(\u{41)----");
+    assert_eq!(result, Err(vec![CompileError::new(CompileErrorKind::InvalidUnicodeEscape(r"\u{41".to_string()), 1, 3)]));
+}
+
+#[test]
+fn test_number_literals() {
+    let mut runtime = Runtime::new();
+
+    for (source, value) in [
+        ("(0xFF)----", Value::Int(255)),
+        ("(-0b1010)----", Value::Int(-10)),
+        ("(0o17)----", Value::Int(15)),
+        ("(1_000_000)----", Value::Int(1_000_000)),
+        ("(-0x8000_0000_0000_0000)----", Value::Int(i64::MIN)),
+        ("(1.5e3)----", Value::Float(1500.0)),
+        ("(2_500.25E-2)----", Value::Float(25.0025)),
+        ("(+7)----", Value::Int(7)),
+    ] {
+        assert_eq!(dango_utils::execute_str(&mut runtime, source), Ok(value), "{}", source);
+    }
+
+    // things that only look a bit like numbers are still text
+    for text in ["0x", "0xZZ", "_1", "1__0", "1_", "0b102"] {
+        let result = dango_utils::execute_str(&mut runtime, &format!("(')({})----", text));
+        assert_eq!(result, Ok(Value::String(text.to_string())), "{}", text);
+    }
+
+    let result = dango_utils::compile_str("(1)----\n(0x1_0000_0000_0000_0000)----");
+    assert_eq!(result, Err(vec![CompileError::new(CompileErrorKind::IntegerOverflow("0x1_0000_0000_0000_0000".to_string()), 2, 1)]));
+
+    let result = dango_utils::compile_str("(9223372036854775808)----");
+    assert_eq!(result, Err(vec![CompileError::new(CompileErrorKind::IntegerOverflow("9223372036854775808".to_string()), 1, 1)]));
+}
+
+#[test]
+fn test_labels() {
+    let mut runtime = Runtime::new();
+
+    let source = "(0)----\nlabel count (j)(@count)(while)(<)(10)---- fetch 0 (+)(1)----";
+    assert_eq!(dango_utils::execute_str(&mut runtime, source), Ok(Value::Int(10)));
+
+    // jumping forward, and labels on lines of their own
+    let source = "(j)(@end)----\n(1)----\nlabel end\n(2)----";
+    assert_eq!(dango_utils::execute_str(&mut runtime, source), Ok(Value::Int(2)));
+
+    // labels move with their chunk when it's appended after other lines
+    let mut runtime = Runtime::new();
+    assert_eq!(dango_utils::execute_str_appended(&mut runtime, "(0)----"), Ok(Value::Int(0)));
+    let source = "label count (j)(@count)(while)(<)(10)---- fetch 0 (+)(1)----";
+    assert_eq!(dango_utils::execute_str_appended(&mut runtime, source), Ok(Value::Int(10)));
+
+    let result = dango_utils::compile_str("label a (1)----\n(j)(@b)----\nlabel a");
+    assert_eq!(result, Err(vec![
+        CompileError::new(CompileErrorKind::DuplicateLabel("a".to_string()), 3, 1),
+        CompileError::new(CompileErrorKind::UndefinedLabel("b".to_string()), 2, 4),
+    ]));
+
+    // without a label of that name it's still text
+    let result = dango_utils::execute_str(&mut runtime, "(')(@user)----");
+    assert_eq!(result, Ok(Value::String("@user".to_string())));
+
+    let result = dango_utils::compile_str("label (a)----");
+    assert_eq!(result, Err(vec![CompileError::new(CompileErrorKind::ExpectedLabel, 1, 1)]));
+}
+
+#[test]
+fn test_jump_targets() {
+    let mut runtime = Runtime::new();
+
+    // the target is the dumpling right after `(j)`, and the `Nop` line at the end is still a valid target
+    let result = dango_utils::compile_str("(j)(0)----\n(1)----\n(j)(5)----");
+    assert_eq!(result, Err(vec![
+        CompileError::new(CompileErrorKind::JumpOutOfRange(0, 4), 1, 4),
+        CompileError::new(CompileErrorKind::JumpOutOfRange(5, 4), 3, 4),
+    ]));
+    assert!(dango_utils::compile_str("(j)(3)----\n(1)----").is_ok());
+
+    // appended chunks can jump back to earlier lines of the session, and like whole programs they can jump to the
+    // line after their last one to stop, but not any further
+    assert_eq!(dango_utils::execute_str_appended(&mut runtime, "(1)----"), Ok(Value::Int(1)));
+    assert_eq!(dango_utils::execute_str_appended(&mut runtime, "(j)(2)(while)(<)(3)---- fetch 0 (+)(1)----"), Ok(Value::Int(3)));
+    assert_eq!(dango_utils::execute_str_appended(&mut runtime, "(j)(4)(2)----"), Ok(Value::Int(2)));
+
+    let result = dango_utils::execute_str_appended(&mut runtime, "(j)(6)(2)----");
+    assert_eq!(result, Err(DangoError::Compile(vec![CompileError::new(CompileErrorKind::JumpOutOfRange(6, 5), 1, 4)])));
+
+    // computed targets follow the same rule when they run
+    assert_eq!(dango_utils::execute_str_appended(&mut runtime, "(j)(+)(1)(4)(7)----"), Ok(Value::Int(7)));
+    let result = without_trace(dango_utils::execute_str_appended(&mut runtime, "(j)(+)(2)(5)----"));
+    assert_eq!(result, Err(DangoError::Runtime(RuntimeError::JumpOutOfRange(7, 6), None)));
+
+    let result = without_trace(dango_utils::execute_str(&mut runtime, "(j)(-)(1)(1)----"));
+    assert_eq!(result, Err(DangoError::Runtime(RuntimeError::JumpOutOfRange(0, 2), None)));
+}
+
+#[test]
+fn test_imports() {
+    use std::path::Path;
+
+    use dango_errors::source_map::SourceMap;
+    use dango_utils::loader::load_str;
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/imports");
+    let mut sources = SourceMap::new();
+    let mut load = |name: &str| load_str(&mut sources, &dir.join(name), &std::fs::read_to_string(dir.join(name)).unwrap());
+
+    // `lib/double.dango` jumps by line number and imports a file with a label, and is imported twice
+    let mut runtime = Runtime::new();
+    let stdout = dango_runtime::streams::SharedBuffer::new();
+    runtime.set_stdout(Box::new(stdout.clone()));
+
+    let program = load("main.dango").unwrap();
+    assert_eq!(runtime.run(program), Ok(Value::Nil));
+    assert_eq!(stdout.take(), "3007\n");
+
+    let errors = load("cycle-a.dango").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].kind, CompileErrorKind::CyclicImport("cycle-a.dango".to_string()));
+    assert_eq!((errors[0].line, errors[0].column), (2, 1));
+
+    let mut sources = SourceMap::new();
+    let source = "(1)----\nimport lib/double.dango\n(+)(1)----";
+    let program = load_str(&mut sources, &dir.join("inline.dango"), source).unwrap();
+    assert_eq!(runtime.run(program), Ok(Value::Int(1004)));
+
+    let errors = load_str(&mut sources, &dir.join("inline.dango"), "(1)---- import lib/inc.dango").unwrap_err();
+    assert_eq!((&errors[0].kind, errors[0].line, errors[0].column), (&CompileErrorKind::ImportNotAlone, 1, 9));
+
+    // a computed line number would be moved along with the imported file and land somewhere else
+    let errors = load_str(&mut sources, &dir.join("inline.dango"), "import lib/computed.dango").unwrap_err();
+    assert_eq!((&errors[0].kind, errors[0].line, errors[0].column), (&CompileErrorKind::ComputedJumpInImport, 2, 1));
+    assert_eq!(sources.name(errors[0].file), dir.join("lib/computed.dango").display().to_string());
+
+    let errors = load_str(&mut sources, &dir.join("inline.dango"), "import missing.dango").unwrap_err();
+    assert!(matches!(&errors[0].kind, CompileErrorKind::ImportFailed(path, _) if path == "missing.dango"));
+
+    // without a loader there is nothing to import from
+    let result = dango_utils::compile_str("import lib/inc.dango");
+    assert!(matches!(&result.unwrap_err()[0].kind, CompileErrorKind::ImportFailed(..)));
+}
+
+#[test]
+fn test_source_maps() {
+    use std::path::Path;
+
+    use dango_errors::render::Renderer;
+    use dango_errors::source_map::SourceMap;
+    use dango_utils::loader::load_str;
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/imports");
+    let mut sources = SourceMap::new();
+
+    // errors in an imported file point at that file
+    let errors = load_str(&mut sources, &dir.join("cycle-a.dango"), "import (cycle-b.dango)").unwrap_err();
+    assert_eq!(sources.name(errors[0].file), dir.join("cycle-b.dango").display().to_string());
+
+    let rendered = Renderer::with_sources(&sources, false).render(&DangoError::Compile(errors.clone()));
+    assert!(rendered.contains(&format!(" --> {}:2:1\n", dir.join("cycle-b.dango").display())));
+    assert!(rendered.contains("2 | import (cycle-a.dango)"));
+
+    let json = DangoError::Compile(errors).diagnostics_in(&sources)[0].to_json();
+    assert!(json.contains(&format!("\"file\":\"{}\"", dir.join("cycle-b.dango").display())));
+
+    // so do runtime errors, once the file is linked into the program
+    let mut runtime = Runtime::new();
+    let mut sources = SourceMap::new();
+    let program = load_str(&mut sources, &dir.join("inline.dango"), "(')(x)----\nimport lib/double.dango").unwrap();
+    let err = runtime.run(program).unwrap_err();
+    let err = DangoError::Runtime(err, runtime.trace().cloned().map(Box::new));
+
+    let rendered = Renderer::with_sources(&sources, false).render(&err);
+    assert!(rendered.contains(&format!(" --> {}:3:1\n", dir.join("lib/double.dango").display())));
+    assert!(rendered.contains("3 | (+)(1000)----"));
+
+    // jumps are lines of the files, not of the program they were linked into
+    let (main, double) = (dir.join("inline.dango").display().to_string(), dir.join("lib/double.dango").display().to_string());
+    let jumps = format!("recent jumps (latest first): {double}:2 -> {double}:3, {main}:2 -> {double}:1\n");
+    assert!(rendered.contains(&jumps), "{}", rendered);
+
+    // a single string has no name to print
+    let source = "(1)----\n(+)(2)(x----";
+    let err = dango_utils::execute_str(&mut runtime, source).unwrap_err();
+    assert!(Renderer::new(source, false).render(&err).contains(" --> 2:7\n"));
+}
+
+#[test]
+fn test_unicode() {
+    use std::path::Path;
+
+    use dango_errors::source_map::{SourceId, SourceMap};
+    use dango_parser::span_tokenizer::{SpanKind, tokenize_into_spans};
+    use dango_runtime::streams::SharedBuffer;
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/unicode");
+    let corpus = [
+        ("greeting.dango", "こんにちは、世界！"),
+        ("escapes.dango", "🍡 ∈ (団子)\n"),
+        ("labels.dango", "🍡3"),
+        ("comments.dango", "🍡 Grüße"),
+    ];
+
+    for (name, output) in corpus {
+        let mut runtime = Runtime::new();
+        let stdout = SharedBuffer::new();
+        runtime.set_stdout(Box::new(stdout.clone()));
+
+        let source = std::fs::read_to_string(dir.join(name)).unwrap();
+        let program = dango_utils::loader::load_str(&mut SourceMap::new(), &dir.join(name), &source).unwrap();
+        assert_eq!(runtime.run(program), Ok(Value::Nil), "{}", name);
+        assert_eq!(stdout.take(), output, "{}", name);
+    }
+
+    // columns count `char`s, and diagnostics also give UTF-16 code units for editors
+    let spans = tokenize_into_spans("(')(🍡)(é)----", SourceId::default()).unwrap();
+    let spans = spans.iter().map(|span| (span.text, span.column)).collect::<Vec<_>>();
+    assert_eq!(spans, [("'", 1), ("🍡", 4), ("é", 7), ("----", 10), ("", 14)]);
+    assert_eq!(tokenize_into_spans("eat (')(団子)---- ;: ✓ :;", SourceId::default()).unwrap()[4].kind, SpanKind::Comment);
+
+    let source = "(')(🍡)(x----";
+    let err = dango_utils::execute_str(&mut Runtime::new(), source).unwrap_err();
+    assert_eq!(err, DangoError::Compile(vec![CompileError::new(CompileErrorKind::UnterminatedDumpling, 1, 7)]));
+    assert_eq!(err.diagnostics(source)[0].utf16_columns, Some((8, 14)));
+}
+
+#[test]
+fn test_display_width() {
+    use crate::editor::display_width;
+
+    let width = |text: &str| display_width(&text.chars().collect::<Vec<char>>());
+
+    assert_eq!(width("(1)----"), 7);
+    assert_eq!(width("(')(団子)"), 9);
+    assert_eq!(width("🍡e\u{301}"), 3);
+}